@@ -18,12 +18,9 @@
 
 use pleaser::*;
 
-use std::os::unix::process::CommandExt;
-use std::process::Command;
-
 use std::collections::HashMap;
 
-use getopts::Options;
+use getopts::{Matches, Options};
 
 use uzers::*;
 
@@ -79,6 +76,16 @@ fn do_list(ro: &mut RunOptions, vec_eo: &[EnvOptions], service: &str) {
     list(vec_eo, ro);
 }
 
+/// show the closest section to a denied request, if the user may list their own rules
+fn do_explain(ro: &RunOptions, vec_eo: &[EnvOptions], service: &str) {
+    let list_entry = own_list_entry(vec_eo, ro);
+    if list_entry.permit() && !challenge_password(ro, &list_entry, service) {
+        return;
+    }
+
+    println!("{}", explain(vec_eo, ro));
+}
+
 /// navigate to directory or exit 1
 fn do_dir_changes(ro: &RunOptions, service: &str) {
     if ro.directory.is_some() {
@@ -94,13 +101,132 @@ fn do_dir_changes(ro: &RunOptions, service: &str) {
     }
 }
 
+/// evaluate a request for any user, host and time, then exit without running it
+fn do_simulate(ro: &RunOptions, matches: &Matches) {
+    let root_uid = nix::unistd::Uid::from_raw(0);
+    let config = match matches.opt_str("c") {
+        Some(config) => config,
+        None => {
+            if nix::unistd::getuid() != root_uid {
+                println!("Only root may simulate /etc/please.ini, use -c to check a copy");
+                std::process::exit(1);
+            }
+            "/etc/please.ini".to_string()
+        }
+    };
+
+    // other users may only simulate files they can read
+    if nix::unistd::getuid() != root_uid && !drop_privs(ro) {
+        std::process::exit(1);
+    }
+
+    let mut sim = RunOptions::new();
+    sim.name = matches.opt_str("simulate").unwrap();
+    sim.syslog = false;
+    sim.new_args.clone_from(&matches.free);
+    sim.reason = matches.opt_str("r");
+    sim.target_group = matches.opt_str("g");
+    sim.directory = matches.opt_str("d");
+    sim.hostname = match matches.opt_str("hostname") {
+        Some(hostname) => hostname,
+        None => nix::unistd::gethostname()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "localhost".to_string()),
+    };
+
+    if let Some(target) = matches.opt_str("u").or_else(|| matches.opt_str("t")) {
+        sim.target = target;
+    }
+
+    if let Some(list) = matches.opt_str("a") {
+        sim.allow_env_list = Some(
+            list.split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.to_string())
+                .collect(),
+        );
+    }
+
+    if let Some(date) = matches.opt_str("date") {
+        match parse_date(&date) {
+            Some(date) => sim.date = date,
+            None => {
+                println!(
+                    "Could not parse date {}, use YYYYmmdd or YYYYmmddHHMMSS",
+                    date
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    sim.groups = match matches.opt_str("groups") {
+        Some(list) => list
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                let gid = get_group_by_name(s).map_or(0, |g| g.gid());
+                (s.to_string(), gid)
+            })
+            .collect(),
+        None => match get_user_by_name(&sim.name) {
+            Some(user) => group_hash(user.groups().unwrap_or_default()),
+            None => HashMap::new(),
+        },
+    };
+
+    if matches.opt_present("l") {
+        sim.acl_type = Acltype::List;
+        if sim.target.is_empty() {
+            sim.target.clone_from(&sim.name);
+        }
+    } else if matches.opt_present("edit") {
+        sim.acl_type = Acltype::Edit;
+    }
+    if sim.target.is_empty() {
+        sim.target = "root".to_string();
+    }
+
+    if sim.new_args.is_empty() {
+        sim.new_args = vec!["".to_string()];
+    }
+    sim.command = replace_new_args(sim.new_args.clone());
+
+    let vec_eo = match parse_ini_config_file(&config, &sim) {
+        Ok(vec_eo) => vec_eo,
+        Err(errors) => {
+            for error in errors {
+                println!("{}", error);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    sim.trace = Some(vec![]);
+    let simulation = simulate(&vec_eo, &mut sim);
+    println!(
+        "request: {} on {} at {} as {}{}: {}",
+        sim.name,
+        sim.hostname,
+        sim.date.format("%Y-%m-%d %H:%M:%S"),
+        sim.target,
+        match &sim.target_group {
+            Some(group) => format!(":{}", group),
+            None => "".to_string(),
+        },
+        sim.command
+    );
+    println!("{}", simulation);
+    for trace in sim.trace.as_ref().unwrap() {
+        println!("trace: {}", trace);
+    }
+
+    std::process::exit(if simulation.permit { 0 } else { 1 });
+}
+
 /// setup getopts for argument parsing and help output
-fn general_options(
-    ro: &mut RunOptions,
-    args: Vec<String>,
-    service: &str,
-    vec_eo: &mut Vec<EnvOptions>,
-) {
+fn general_options(ro: &mut RunOptions, args: Vec<String>, service: &str) {
     let mut opts = Options::new();
     opts.parsing_style(getopts::ParsingStyle::StopAtFirstFree);
     opts.optopt(
@@ -112,8 +238,15 @@ fn general_options(
     opts.optopt("c", "check", "check config file", "FILE");
     opts.optopt("d", "dir", "change to directory prior to execution", "DIR");
     opts.optopt("g", "group", "become target group", "GROUP");
+    opts.optflag("", "explain", "show why a request was denied");
     opts.optflag("h", "help", "print usage help");
     opts.optflag("l", "list", "list effective rules, can combine with -t/-u");
+    opts.optflag("", "lint", "report questionable rules, use with -c");
+    opts.optflag(
+        "",
+        "digest",
+        "show the digest of each run section's program, use with -c",
+    );
     opts.optflag("n", "noprompt", "do nothing if a password is required");
     opts.optflag("p", "purge", "purge access token");
     opts.optopt("r", "reason", "provide reason for execution", "REASON");
@@ -121,6 +254,21 @@ fn general_options(
     opts.optopt("u", "user", "become target user", "USER");
     opts.optflag("v", "version", "print version and exit");
     opts.optflag("w", "warm", "warm access token and exit");
+    opts.optopt(
+        "",
+        "simulate",
+        "evaluate a request as USER without running it",
+        "USER",
+    );
+    opts.optopt(
+        "",
+        "groups",
+        "with --simulate, comma separated groups",
+        "LIST",
+    );
+    opts.optopt("", "hostname", "with --simulate, the host to check", "HOST");
+    opts.optopt("", "date", "with --simulate, YYYYmmdd[HHMMSS]", "DATE");
+    opts.optflag("", "edit", "with --simulate, check edit rules");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -130,17 +278,65 @@ fn general_options(
         }
     };
 
+    if matches.opt_present("simulate") {
+        do_simulate(ro, &matches);
+    }
+
+    if matches.opt_present("lint") && !matches.opt_present("c") {
+        println!("--lint needs a config file to check with -c");
+        std::process::exit(1);
+    }
+
+    if matches.opt_present("digest") && !matches.opt_present("c") {
+        println!("--digest needs a config file to check with -c");
+        std::process::exit(1);
+    }
+
+    // -c is read as root, so hashing the files rules name would tell anyone their digest
+    if matches.opt_present("digest") && !nix::unistd::getuid().is_root() {
+        println!("--digest may only be used by root");
+        std::process::exit(1);
+    }
+
     if matches.opt_present("c") {
-        let mut bytes = 0;
-        let mut ini_list: HashMap<String, bool> = HashMap::new();
-        std::process::exit(read_ini_config_file(
-            &matches.opt_str("c").unwrap(),
-            vec_eo,
-            ro,
-            true,
-            &mut bytes,
-            &mut ini_list,
-        ) as i32);
+        let (parsed, signatures) =
+            parse_ini_config_file_signatures(&matches.opt_str("c").unwrap(), ro);
+        for signature in &signatures {
+            println!("{}", signature);
+        }
+
+        match parsed {
+            Ok(vec_eo) => {
+                for warning in config_warnings(&vec_eo) {
+                    println!("{}", warning);
+                }
+
+                if matches.opt_present("digest") {
+                    for line in digest_report(&vec_eo) {
+                        println!("{}", line);
+                    }
+                }
+
+                if !matches.opt_present("lint") {
+                    std::process::exit(0);
+                }
+
+                let findings = lint_config(&vec_eo, ro);
+                for finding in &findings {
+                    println!("{}", finding);
+                }
+                if findings.iter().any(|f| f.severity == LintSeverity::Error) {
+                    std::process::exit(1);
+                }
+                std::process::exit(0);
+            }
+            Err(errors) => {
+                for error in errors {
+                    println!("{}", error);
+                }
+                std::process::exit(1);
+            }
+        }
     }
 
     let root_uid = nix::unistd::Uid::from_raw(0);
@@ -173,6 +369,9 @@ fn general_options(
     if matches.opt_present("l") {
         ro.acl_type = Acltype::List;
     }
+    if matches.opt_present("explain") {
+        ro.explain = true;
+    }
 
     let header = format!("{} [arguments] </path/to/executable>", &service);
     common_opt_arguments(&matches, &opts, ro, service, &header);
@@ -217,7 +416,6 @@ fn main() {
     ro.name = original_user.name().to_string_lossy().to_string();
     ro.syslog = true;
     ro.original_command.clone_from(&args);
-    let mut vec_eo: Vec<EnvOptions> = vec![];
 
     let root_uid = nix::unistd::Uid::from_raw(0);
     let root_gid = nix::unistd::Gid::from_raw(0);
@@ -231,7 +429,7 @@ fn main() {
         }
     }
 
-    general_options(&mut ro, args, &service, &mut vec_eo);
+    general_options(&mut ro, args, &service);
 
     clean_environment(&mut ro);
 
@@ -240,19 +438,16 @@ fn main() {
         std::process::exit(1);
     }
 
-    let mut bytes = 0;
-    let mut ini_list: HashMap<String, bool> = HashMap::new();
-    if read_ini_config_file(
-        "/etc/please.ini",
-        &mut vec_eo,
-        &ro,
-        true,
-        &mut bytes,
-        &mut ini_list,
-    ) {
-        println!("Exiting due to error, cannot fully process /etc/please.ini");
-        std::process::exit(1);
-    }
+    let vec_eo = match parse_ini_config_file_cached("/etc/please.ini", &ro, &cache_dir()) {
+        Ok(vec_eo) => vec_eo,
+        Err(errors) => {
+            for error in errors {
+                println!("{}", error);
+            }
+            println!("Exiting due to error, cannot fully process /etc/please.ini");
+            std::process::exit(1);
+        }
+    };
 
     if !drop_privs(&ro) {
         std::process::exit(1);
@@ -287,9 +482,60 @@ fn main() {
         exit_if_command_not_found(&ro, &service);
 
         print_may_not(&ro);
+        if ro.explain {
+            do_explain(&ro, &vec_eo, &service);
+        }
+        std::process::exit(1);
+    }
+
+    // the checks are made on the file that will be run, not a path that could change
+    let binary = match OpenBinary::open_resolved(&ro.cloned_args.as_ref().unwrap()[0]) {
+        Ok(binary) => binary,
+        Err(x) => {
+            log_action(&service, "deny", &ro, &ro.original_command.join(" "));
+            println!(
+                "[{}]: cannot open {}: {}",
+                &service,
+                &ro.cloned_args.as_ref().unwrap()[0],
+                x
+            );
+            std::process::exit(1);
+        }
+    };
+
+    // others must not be able to replace what root runs
+    if let Some(fault) = unsafe_binary(&entry, &binary) {
+        log_action(
+            &service,
+            "unsafe_binary",
+            &ro,
+            &ro.original_command.join(" "),
+        );
+        println!("[{}]: {}", &service, fault);
         std::process::exit(1);
     }
 
+    if let Some(digests) = &entry.digest {
+        let digest = match as_root(&ro, || binary.digest()) {
+            Ok(digest) => digest,
+            Err(x) => {
+                log_action(&service, "digest_fail", &ro, &ro.original_command.join(" "));
+                println!("[{}]: cannot read {}: {}", &service, &binary.path, x);
+                std::process::exit(1);
+            }
+        };
+        if !digests.contains(&digest) {
+            log_action(
+                &service,
+                "digest_mismatch",
+                &ro,
+                &ro.original_command.join(" "),
+            );
+            println!("[{}]: {} has changed", &service, &binary.path);
+            std::process::exit(1);
+        }
+    }
+
     // check if a reason was given
     if !reason_ok(&entry, &ro) {
         log_action(&service, "reason_fail", &ro, &ro.original_command.join(" "));
@@ -312,7 +558,11 @@ fn main() {
         println!("Could not lookup {}", &ro.target);
         std::process::exit(1);
     }
-    let lookup_name = lookup_name.unwrap();
+    let mut lookup_name = lookup_name.unwrap();
+    // capabilities are all root would be needed for, so run as the invoking user
+    if entry.capabilities.is_some() && lookup_name.uid() == 0 {
+        lookup_name = original_user.clone();
+    }
     let target_uid = nix::unistd::Uid::from_raw(lookup_name.uid());
     let target_gid = runopt_target_gid(&ro, &lookup_name);
 
@@ -330,27 +580,125 @@ fn main() {
         std::process::exit(1);
     }
 
+    // record the session, its id goes in the log so the two can be found together
+    let mut recording = None;
+    if entry.record == Some(true) {
+        if !esc_privs() {
+            std::process::exit(1);
+        }
+        let session = pleaser::record::create_session(&pleaser::record::session_dir(), &ro);
+        if !drop_privs(&ro) {
+            std::process::exit(1);
+        }
+        match session {
+            Ok(session) => {
+                ro.session = Some(session.id.clone());
+                recording = Some(session);
+            }
+            Err(x) => {
+                log_action(&service, "record_fail", &ro, &ro.original_command.join(" "));
+                println!("[{}]: could not record session: {}", &service, x);
+                std::process::exit(1);
+            }
+        }
+    }
+
     log_action(&service, "permit", &ro, &ro.original_command.join(" "));
 
     set_environment(&ro, &entry, &original_user, original_uid, &lookup_name);
 
+    // keep the command away from the user's terminal, pass on what it reads and writes when
+    // recording, and wait to log how it ended when supervising
+    if entry.use_pty == Some(true) || recording.is_some() || entry.supervise == Some(true) {
+        match pleaser::pty::fork_pty(&ro, &entry, recording) {
+            Err(x) => {
+                log_action(&service, "pty_fail", &ro, &ro.original_command.join(" "));
+                println!("[{}]: could not start the command: {}", &service, x);
+                std::process::exit(1);
+            }
+            Ok(Some(outcome)) => {
+                if entry.supervise == Some(true) {
+                    ro.outcome = Some(outcome.describe());
+                    log_action(&service, "complete", &ro, &ro.original_command.join(" "));
+                }
+                outcome.exit();
+            }
+            Ok(None) => {}
+        }
+    }
+
     if !esc_privs() {
         std::process::exit(1);
     }
 
-    if !set_privs(&ro.target, target_uid, target_gid) {
+    if let Err(x) = set_limits(&entry) {
+        log_action(&service, "limits_fail", &ro, &ro.original_command.join(" "));
+        println!("[{}]: could not set limits: {}", &service, x);
         std::process::exit(1);
     }
 
+    if let Err(x) = isolate(&entry, ro.directory.as_deref()) {
+        log_action(
+            &service,
+            "isolate_fail",
+            &ro,
+            &ro.original_command.join(" "),
+        );
+        println!("[{}]: could not isolate: {}", &service, x);
+        std::process::exit(1);
+    }
+
+    // the permitted binary may run, but nothing that it starts
+    if entry.noexec == Some(true) {
+        if let Err(x) = binary.noexec() {
+            log_action(&service, "noexec_fail", &ro, &ro.original_command.join(" "));
+            println!("[{}]: could not set noexec: {}", &service, x);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(capabilities) = &entry.capabilities {
+        if let Err(x) = keep_capabilities(capabilities) {
+            log_action(
+                &service,
+                "capabilities_fail",
+                &ro,
+                &ro.original_command.join(" "),
+            );
+            println!("[{}]: could not set capabilities: {}", &service, x);
+            std::process::exit(1);
+        }
+    }
+
+    if !set_privs(
+        &lookup_name.name().to_string_lossy(),
+        target_uid,
+        target_gid,
+    ) {
+        std::process::exit(1);
+    }
+
+    if let Some(capabilities) = &entry.capabilities {
+        if let Err(x) = raise_capabilities(capabilities) {
+            log_action(
+                &service,
+                "capabilities_fail",
+                &ro,
+                &ro.original_command.join(" "),
+            );
+            println!("[{}]: could not set capabilities: {}", &service, x);
+            std::process::exit(1);
+        }
+    }
+
     nix::sys::stat::umask(ro.old_umask.unwrap());
 
-    if ro.cloned_args.as_ref().unwrap().len() > 1 {
-        Command::new(&ro.cloned_args.as_ref().unwrap()[0])
-            .args(ro.cloned_args.as_ref().unwrap().clone().split_off(1))
-            .exec();
-    } else {
-        Command::new(&ro.cloned_args.as_ref().unwrap()[0]).exec();
+    // the program sees the name it was found by, not where a link leads
+    let mut args = ro.cloned_args.clone().unwrap();
+    if let Some(Some(located)) = ro.located_bin.get(&ro.new_args[0]) {
+        args[0].clone_from(located);
     }
-    println!("Error executing");
+    let why = binary.exec(&args);
+    println!("Error executing: {}", why);
     std::process::exit(1);
 }
@@ -30,7 +30,6 @@ use std::path::Path;
 use std::process::{Command, Stdio};
 
 use regex::Regex;
-use std::collections::HashMap;
 
 use getopts::Options;
 
@@ -557,7 +556,6 @@ fn main() {
     ro.acl_type = Acltype::Edit;
     ro.syslog = true;
     ro.original_command.clone_from(&args);
-    let mut vec_eo: Vec<EnvOptions> = vec![];
 
     let root_uid = nix::unistd::Uid::from_raw(0);
     let root_gid = nix::unistd::Gid::from_raw(0);
@@ -582,19 +580,16 @@ fn main() {
     if !esc_privs() {
         std::process::exit(1);
     }
-    let mut bytes = 0;
-    let mut ini_list: HashMap<String, bool> = HashMap::new();
-    if read_ini_config_file(
-        "/etc/please.ini",
-        &mut vec_eo,
-        &ro,
-        true,
-        &mut bytes,
-        &mut ini_list,
-    ) {
-        println!("Exiting due to error, cannot fully process /etc/please.ini");
-        std::process::exit(1);
-    }
+    let vec_eo = match parse_ini_config_file_cached("/etc/please.ini", &ro, &cache_dir()) {
+        Ok(vec_eo) => vec_eo,
+        Err(errors) => {
+            for error in errors {
+                println!("{}", error);
+            }
+            println!("Exiting due to error, cannot fully process /etc/please.ini");
+            std::process::exit(1);
+        }
+    };
 
     if !drop_privs(&ro) {
         std::process::exit(1);
@@ -0,0 +1,151 @@
+//    pleasereplay
+//    Copyright (C) 2020-2021 ed neville
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! pleasereplay.rs list and replay sessions recorded by please
+
+use pleaser::record::*;
+use pleaser::*;
+
+use std::time::Duration;
+
+use getopts::Options;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let service = String::from("pleasereplay");
+
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print usage help");
+    opts.optflag("v", "version", "print version and exit");
+    opts.optflag("l", "list", "list sessions");
+    opts.optopt("u", "user", "list sessions of USER", "USER");
+    opts.optopt("t", "target", "list sessions run as TARGET", "TARGET");
+    opts.optopt("d", "date", "list sessions started on DATE", "YYYYmmdd");
+    opts.optopt("s", "speed", "replay SPEED times faster", "SPEED");
+    opts.optopt(
+        "m",
+        "max-wait",
+        "wait at most SECONDS between output",
+        "SECONDS",
+    );
+    opts.optflag("i", "input", "replay input as well as output");
+    opts.optopt("D", "dir", "sessions are in DIR", "DIR");
+
+    let header = format!("{} [arguments] [-l|session]", &service);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(x) => {
+            println!("{}", x);
+            print_usage(&opts, &header);
+            std::process::exit(1);
+        }
+    };
+
+    if matches.opt_present("h") {
+        print_usage(&opts, &header);
+        print_version(&service);
+        std::process::exit(0);
+    }
+
+    if matches.opt_present("v") {
+        print_version(&service);
+        std::process::exit(0);
+    }
+
+    let dir = matches.opt_str("D").unwrap_or_else(session_dir);
+
+    if matches.opt_present("l") {
+        let sessions = match list_sessions(
+            &dir,
+            matches.opt_str("u").as_deref(),
+            matches.opt_str("t").as_deref(),
+            matches.opt_str("d").as_deref(),
+        ) {
+            Ok(sessions) => sessions,
+            Err(x) => {
+                eprintln!("Could not read {}: {}", dir, x);
+                std::process::exit(1);
+            }
+        };
+        for session in sessions {
+            println!(
+                "{} {} -> {} {} exit={}: {}",
+                session.id,
+                session.user,
+                session.target,
+                session.date,
+                if session.exit.is_empty() {
+                    "unknown"
+                } else {
+                    &session.exit
+                },
+                session.command
+            );
+        }
+        std::process::exit(0);
+    }
+
+    if matches.free.len() != 1 {
+        print_usage(&opts, &header);
+        std::process::exit(1);
+    }
+    let id = &matches.free[0];
+    if !valid_session_id(&dir, id) {
+        eprintln!("No session {} in {}", id, dir);
+        std::process::exit(1);
+    }
+
+    let speed = match matches.opt_str("s") {
+        None => 1.0,
+        Some(speed) => match speed.parse::<f64>() {
+            Ok(speed) if speed > 0.0 => speed,
+            _ => {
+                eprintln!("Could not parse speed {}", speed);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let max_wait = match matches.opt_str("m") {
+        None => None,
+        Some(max_wait) => match max_wait.parse::<f64>() {
+            Ok(max_wait) if max_wait >= 0.0 => Some(Duration::from_secs_f64(max_wait)),
+            _ => {
+                eprintln!("Could not parse max-wait {}", max_wait);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let events = match read_session(&dir, id) {
+        Ok(events) => events,
+        Err(x) => {
+            eprintln!("Could not read session {}: {}", id, x);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(x) = replay(
+        &events,
+        speed,
+        max_wait,
+        matches.opt_present("i"),
+        &mut std::io::stdout(),
+    ) {
+        eprintln!("Could not replay session {}: {}", id, x);
+        std::process::exit(1);
+    }
+}
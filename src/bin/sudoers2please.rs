@@ -0,0 +1,89 @@
+//    sudoers2please
+//    Copyright (C) 2020-2021 ed neville
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! sudoers2please.rs convert a sudoers file into please.ini sections
+
+use pleaser::sudoers::convert_sudoers;
+use pleaser::*;
+
+use std::io::Read;
+
+use getopts::Options;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let service = String::from("sudoers2please");
+
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print usage help");
+    opts.optflag("v", "version", "print version and exit");
+
+    let header = format!("{} [arguments] [/etc/sudoers ...]", &service);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(x) => {
+            println!("{}", x);
+            print_usage(&opts, &header);
+            std::process::exit(1);
+        }
+    };
+
+    if matches.opt_present("h") {
+        print_usage(&opts, &header);
+        print_version(&service);
+        std::process::exit(0);
+    }
+
+    if matches.opt_present("v") {
+        print_version(&service);
+        std::process::exit(0);
+    }
+
+    let files = if matches.free.is_empty() {
+        vec!["/etc/sudoers".to_string()]
+    } else {
+        matches.free
+    };
+
+    let mut failed = false;
+    for file in files {
+        let mut sudoers = String::new();
+        let read = if file == "-" {
+            std::io::stdin().read_to_string(&mut sudoers)
+        } else {
+            std::fs::File::open(&file).and_then(|mut f| f.read_to_string(&mut sudoers))
+        };
+
+        match read {
+            Ok(_) => {
+                let (ini, errors) = convert_sudoers(&sudoers, &file);
+                print!("{}", ini);
+                for error in &errors {
+                    eprintln!("{}: {}", file, error);
+                }
+                failed |= !errors.is_empty();
+            }
+            Err(x) => {
+                eprintln!("Could not open {}: {}", file, x);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
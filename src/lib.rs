@@ -14,15 +14,22 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use regex::Regex;
-use std::collections::HashMap;
+pub mod pty;
+pub mod record;
+pub mod sudoers;
+
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
 use std::env;
 use std::ffi::{CStr, CString};
 use std::path::Path;
 use std::process;
 use syslog::{Facility, Formatter3164};
 
-use chrono::{NaiveDate, NaiveDateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use nix::sys::signal;
 use nix::sys::signal::*;
 
@@ -31,6 +38,8 @@ use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::os::unix::io::AsRawFd;
 use std::time::SystemTime;
 use uzers::os::unix::UserExt;
@@ -43,27 +52,57 @@ use pam::Authenticator;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
 pub enum EditMode {
     Mode(i32),
     Keep(bool),
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
 pub enum ReasonType {
     Need(bool),
     Text(String),
 }
 
+/// regexes compiled when the section was read, keyed by the pattern they came from
 #[derive(Clone, Debug)]
+pub struct RegexCache {
+    pub user: String,
+    pub hostname: String,
+    pub patterns: HashMap<String, Regex>,
+}
+
+impl RegexCache {
+    pub fn new(ro: &RunOptions) -> RegexCache {
+        RegexCache {
+            user: ro.name.clone(),
+            hostname: ro.hostname.clone(),
+            patterns: HashMap::new(),
+        }
+    }
+}
+
+/// a start and end date, both included, in UTC
+pub type DateRange = (NaiveDateTime, NaiveDateTime);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EnvOptions {
     pub name: Option<String>,
     pub exact_name: Option<String>,
     pub rule: Option<String>,
     pub exact_rule: Option<String>,
+    pub args: Option<BTreeMap<usize, String>>,
+    pub args_max: Option<usize>,
+    pub args_rest: Option<String>,
     pub notbefore: Option<NaiveDateTime>,
     pub notafter: Option<NaiveDateTime>,
     pub datematch: Option<String>,
+    pub schedule: Option<String>,
+    pub exclude_dates_file: Option<String>,
+    pub exclude_dates: Option<Vec<DateRange>>,
     pub target: Option<String>,
     pub exact_target: Option<String>,
     pub target_group: Option<String>,
@@ -88,7 +127,30 @@ pub struct EnvOptions {
     pub env_assign: Option<HashMap<String, String>>,
     pub timeout: Option<u32>,
     pub search_path: Option<String>,
+    pub digest: Option<Vec<String>>,
+    pub allow_unsafe_binary: Option<bool>,
+    pub resolve_links: Option<bool>,
+    pub noexec: Option<bool>,
+    pub rlimit: Option<BTreeMap<String, u64>>,
+    pub nice: Option<i32>,
+    pub ioprio: Option<String>,
+    pub chroot: Option<String>,
+    pub private_tmp: Option<bool>,
+    pub readonly_paths: Option<Vec<String>>,
+    pub hide_paths: Option<Vec<String>>,
+    pub capabilities: Option<Vec<String>>,
+    pub use_pty: Option<bool>,
+    pub record: Option<bool>,
+    pub supervise: Option<bool>,
     pub token_timeout: Option<u64>,
+    pub timezone: Option<String>,
+    #[serde(skip)]
+    pub regex_cache: Option<RegexCache>,
+    /// the schedule parsed when the section was read
+    #[serde(skip)]
+    pub schedule_cache: Option<Schedule>,
+    pub inherit: Option<String>,
+    pub line: Option<usize>,
 }
 
 impl EnvOptions {
@@ -98,6 +160,9 @@ impl EnvOptions {
             exact_name: None,
             rule: Some("^$".to_string()),
             exact_rule: None,
+            args: None,
+            args_max: None,
+            args_rest: None,
             target: Some("root".to_string()),
             exact_target: None,
             target_group: None,
@@ -105,6 +170,9 @@ impl EnvOptions {
             notbefore: None,
             notafter: None,
             datematch: None,
+            schedule: None,
+            exclude_dates_file: None,
+            exclude_dates: None,
             hostname: None,
             exact_hostname: None,
             file_name: "".to_string(),
@@ -125,7 +193,27 @@ impl EnvOptions {
             env_assign: None,
             timeout: None,
             search_path: None,
+            digest: None,
+            allow_unsafe_binary: None,
+            resolve_links: None,
+            noexec: None,
+            rlimit: None,
+            nice: None,
+            ioprio: None,
+            chroot: None,
+            private_tmp: None,
+            readonly_paths: None,
+            hide_paths: None,
+            capabilities: None,
+            use_pty: None,
+            record: None,
+            supervise: None,
             token_timeout: None,
+            timezone: None,
+            regex_cache: None,
+            schedule_cache: None,
+            inherit: None,
+            line: None,
         }
     }
     fn new_deny() -> EnvOptions {
@@ -143,6 +231,17 @@ impl EnvOptions {
 
         true
     }
+    /// true if any of argN, args_max or args_rest are set
+    pub fn has_args(&self) -> bool {
+        self.args.is_some() || self.args_max.is_some() || self.args_rest.is_some()
+    }
+    /// true if any of chroot, private_tmp, readonly_paths or hide_paths are set
+    pub fn isolated(&self) -> bool {
+        self.chroot.is_some()
+            || self.private_tmp == Some(true)
+            || self.readonly_paths.is_some()
+            || self.hide_paths.is_some()
+    }
     pub fn require_pass(&self) -> bool {
         if self.require_pass.is_some() && !self.require_pass.unwrap() {
             return false;
@@ -184,7 +283,12 @@ pub struct RunOptions {
     pub env_options: Option<EnvOptions>,
     pub cloned_args: Option<Vec<String>>,
     pub located_bin: HashMap<String, Option<String>>,
+    pub located_digest: HashMap<String, Option<String>>,
     pub resume: Option<bool>,
+    pub trace: Option<Vec<MatchTrace>>,
+    pub explain: bool,
+    pub session: Option<String>,
+    pub outcome: Option<String>,
 }
 
 impl RunOptions {
@@ -214,7 +318,12 @@ impl RunOptions {
             env_options: None,
             cloned_args: None,
             located_bin: HashMap::new(),
+            located_digest: HashMap::new(),
             resume: None,
+            trace: None,
+            explain: false,
+            session: None,
+            outcome: None,
         }
     }
 }
@@ -258,7 +367,7 @@ impl pam::Converse for PamConvo {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
 pub enum Acltype {
     Run,
     List,
@@ -289,6 +398,67 @@ pub fn print_may_not(ro: &RunOptions) {
     );
 }
 
+/// largest compiled size permitted for a single regex
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+const MAX_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// replace %{USER} and %{HOSTNAME}, prefix with ^ and suffix with $ and compile
+fn regex_compile(v: &str, ro: &RunOptions) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(&format!(
+        "^{}$",
+        &v.replace("%{USER}", &ro.name)
+            .replace("%{HOSTNAME}", &ro.hostname)
+    ))
+    .size_limit(REGEX_SIZE_LIMIT)
+    .build()
+}
+
+/// use the regex compiled when the section was read if it was bound to the same user and host
+pub fn section_regex<'a>(
+    item: &'a EnvOptions,
+    v: &str,
+    ro: &RunOptions,
+    line: Option<i32>,
+) -> Option<Cow<'a, Regex>> {
+    if let Some(cache) = &item.regex_cache {
+        if cache.user == ro.name && cache.hostname == ro.hostname {
+            if let Some(re) = cache.patterns.get(v) {
+                return Some(Cow::Borrowed(re));
+            }
+        }
+    }
+
+    regex_build(v, ro, &item.file_name, &item.section, line).map(Cow::Owned)
+}
+
+/// compile the match regexes of a section that were not given a value in the config
+fn compile_section_defaults(opt: &mut EnvOptions, ro: &RunOptions) {
+    let cache = opt.regex_cache.get_or_insert_with(|| RegexCache::new(ro));
+
+    for v in [
+        &opt.name,
+        &opt.hostname,
+        &opt.target,
+        &opt.target_group,
+        &opt.rule,
+        &opt.args_rest,
+        &opt.dir,
+        &opt.datematch,
+        &opt.env_permit,
+    ]
+    .iter()
+    .copied()
+    .flatten()
+    {
+        if cache.patterns.contains_key(v) {
+            continue;
+        }
+        if let Ok(re) = regex_compile(v, ro) {
+            cache.patterns.insert(v.to_string(), re);
+        }
+    }
+}
+
 /// build a regex and replace %{USER} with the user str, prefix with ^ and suffix with $
 pub fn regex_build(
     v: &str,
@@ -297,11 +467,7 @@ pub fn regex_build(
     section: &str,
     line: Option<i32>,
 ) -> Option<Regex> {
-    let rule = Regex::new(&format!(
-        "^{}$",
-        &v.replace("%{USER}", &ro.name)
-            .replace("%{HOSTNAME}", &ro.hostname)
-    ));
+    let rule = regex_compile(v, ro);
     if rule.is_err() {
         println!(
             "Error parsing {}{}",
@@ -470,6 +636,99 @@ pub fn common_opt_arguments(
         .expect("Hostname wasn't valid UTF-8");
 }
 
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ConfigErrorKind {
+    UnknownKey,
+    NoSection,
+    BadRegex,
+    BadDate,
+    BadValue,
+    Include,
+    IncludeLoop,
+    IncludeRepeat,
+    Inherit,
+    InheritLoop,
+    Alias,
+    AliasLoop,
+    Permission,
+    Signature,
+    Io,
+    TooLarge,
+}
+
+impl fmt::Display for ConfigErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigErrorKind::UnknownKey => write!(f, "unknown_key"),
+            ConfigErrorKind::NoSection => write!(f, "no_section"),
+            ConfigErrorKind::BadRegex => write!(f, "bad_regex"),
+            ConfigErrorKind::BadDate => write!(f, "bad_date"),
+            ConfigErrorKind::BadValue => write!(f, "bad_value"),
+            ConfigErrorKind::Include => write!(f, "include"),
+            ConfigErrorKind::IncludeLoop => write!(f, "include_loop"),
+            ConfigErrorKind::IncludeRepeat => write!(f, "include_repeat"),
+            ConfigErrorKind::Inherit => write!(f, "inherit"),
+            ConfigErrorKind::InheritLoop => write!(f, "inherit_loop"),
+            ConfigErrorKind::Alias => write!(f, "alias"),
+            ConfigErrorKind::AliasLoop => write!(f, "alias_loop"),
+            ConfigErrorKind::Permission => write!(f, "permission"),
+            ConfigErrorKind::Signature => write!(f, "signature"),
+            ConfigErrorKind::Io => write!(f, "io"),
+            ConfigErrorKind::TooLarge => write!(f, "too_large"),
+        }
+    }
+}
+
+/// a problem found while reading config, fatal errors stop the file being read any further
+#[derive(Clone, Debug)]
+pub struct ConfigError {
+    pub kind: ConfigErrorKind,
+    pub file: String,
+    pub line: Option<usize>,
+    pub section: Option<String>,
+    pub key: Option<String>,
+    pub message: String,
+    pub fatal: bool,
+}
+
+impl ConfigError {
+    pub fn new(kind: ConfigErrorKind, file: &str, message: &str) -> ConfigError {
+        ConfigError {
+            kind,
+            file: file.to_string(),
+            line: None,
+            section: None,
+            key: None,
+            message: message.to_string(),
+            fatal: false,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// position of the line being parsed, used to decorate errors
+struct IniPosition<'a> {
+    file: &'a str,
+    line: usize,
+    section: Option<&'a str>,
+    key: &'a str,
+}
+
+impl IniPosition<'_> {
+    fn error(&self, kind: ConfigErrorKind, message: &str) -> ConfigError {
+        let mut error = ConfigError::new(kind, self.file, message);
+        error.line = Some(self.line);
+        error.section = self.section.map(|s| s.to_string());
+        error.key = Some(self.key.to_string());
+        error
+    }
+}
+
 /// read an ini file and traverse includes
 pub fn read_ini(
     conf: &str,
@@ -480,19 +739,129 @@ pub fn read_ini(
     bytes: &mut u64,
     ini_list: &mut HashMap<String, bool>,
 ) -> bool {
-    let parse_datetime_from_str = NaiveDateTime::parse_from_str;
-    let parse_date_from_str = NaiveDate::parse_from_str;
-    let mut faulty = false;
+    let mut state = IniState::new(bytes, ini_list);
+    parse_ini(conf, vec_eo, ro, config_path, &mut state);
+    print_config_faults(&state.errors, fail_error)
+}
+
+/// print errors for the bool returning readers, true if they are considered faulty
+fn print_config_faults(errors: &[ConfigError], fail_error: bool) -> bool {
+    for error in errors {
+        println!("{}", error);
+    }
+
+    errors.iter().any(|e| e.fatal) || (fail_error && !errors.is_empty())
+}
+
+/// state carried through a config file and its includes
+struct IniState<'a> {
+    bytes: &'a mut u64,
+    ini_list: &'a mut HashMap<String, bool>,
+    sections: HashMap<String, EnvOptions>,
+    aliases: HashMap<String, String>,
+    errors: Vec<ConfigError>,
+    // each file and directory as it was when read, None if one could not be stat'ed
+    sources: Vec<Option<CacheKey>>,
+    file_uid: u32,
+    admin_uids: Vec<u32>,
+    keyring: Option<Vec<SignifyKey>>,
+    signatures: Vec<SignatureStatus>,
+    timezone: Option<String>,
+    exclude_dates_file: Option<String>,
+    // exclude_dates_file ranges keyed on the file and time zone they were read in, None if faulty
+    exclude_dates: HashMap<(String, String), Option<Vec<DateRange>>>,
+}
+
+impl IniState<'_> {
+    fn new<'a>(bytes: &'a mut u64, ini_list: &'a mut HashMap<String, bool>) -> IniState<'a> {
+        IniState {
+            bytes,
+            ini_list,
+            sections: HashMap::new(),
+            aliases: HashMap::new(),
+            errors: vec![],
+            sources: vec![],
+            file_uid: 0,
+            admin_uids: vec![],
+            keyring: None,
+            signatures: vec![],
+            timezone: None,
+            exclude_dates_file: None,
+            exclude_dates: HashMap::new(),
+        }
+    }
+}
+
+/// finish a section, keeping it for later inherit and adding it to vec_eo if it can match
+fn end_section(
+    mut opt: EnvOptions,
+    section_keys: &[(usize, String, String)],
+    vec_eo: &mut Vec<EnvOptions>,
+    ro: &RunOptions,
+    state: &mut IniState,
+) {
+    if opt.section.starts_with("alias:") {
+        return;
+    }
+
+    ini_dates(&mut opt, section_keys, state);
+    // a timezone in a default section is used by the sections that follow it
+    if opt.section.starts_with("default") && opt.timezone.is_some() {
+        state.timezone.clone_from(&opt.timezone);
+    }
+    ini_exclude_dates(&mut opt, section_keys, state);
+
+    // a section that only matches arguments should not need a rule as well
+    if opt.has_args() && opt.exact_rule.is_none() && opt.rule.as_deref() == Some("^$") {
+        opt.rule = Some(".*".to_string());
+    }
+
+    compile_section_defaults(&mut opt, ro);
+    state.sections.insert(opt.section.clone(), opt.clone());
+    if opt.configured {
+        vec_eo.push(opt);
+    }
+}
+
+/// parse an ini string, traversing includes and appending problems to state.errors
+fn parse_ini(
+    conf: &str,
+    vec_eo: &mut Vec<EnvOptions>,
+    ro: &RunOptions,
+    config_path: &str,
+    state: &mut IniState,
+) {
     let mut section = String::from("no section defined");
     let mut in_section = false;
     let mut opt = EnvOptions::new();
-
-    if ini_list.contains_key(config_path) {
-        println!("Error parsing already read file {}", config_path);
-        return false;
+    let mut section_keys: Vec<(usize, String, String)> = vec![];
+
+    // files are true whilst being read, a loop is where we come back to one of those
+    match state.ini_list.get(config_path) {
+        Some(true) => {
+            state.errors.push(ConfigError::new(
+                ConfigErrorKind::IncludeLoop,
+                config_path,
+                &format!("Error parsing already read file {}", config_path),
+            ));
+            return;
+        }
+        // its sections stay where they were first read, not where they are included again
+        Some(false) => {
+            state.errors.push(ConfigError::new(
+                ConfigErrorKind::IncludeRepeat,
+                config_path,
+                &format!(
+                    "Error including {} again, its sections are where it was first read",
+                    config_path
+                ),
+            ));
+            return;
+        }
+        None => {}
     }
 
-    ini_list.insert(config_path.to_string(), true);
+    state.ini_list.insert(config_path.to_string(), true);
 
     for (mut line_number, l) in conf.split('\n').enumerate() {
         line_number += 1;
@@ -503,14 +872,16 @@ pub fn read_ini(
         }
 
         if line.starts_with('[') && line.ends_with(']') {
+            if in_section {
+                end_section(opt, &section_keys, vec_eo, ro, state);
+            }
             in_section = true;
             section = line[1..line.len() - 1].to_string();
-            if opt.configured {
-                vec_eo.push(opt);
-            }
             opt = EnvOptions::new();
             opt.section.clone_from(&section);
             opt.file_name = String::from(config_path);
+            opt.line = Some(line_number);
+            section_keys.clear();
             continue;
         }
 
@@ -522,52 +893,67 @@ pub fn read_ini(
         let key = line[0..equals_pos.unwrap()].trim();
         let value = line[equals_pos.unwrap() + 1..].trim();
 
+        let pos = IniPosition {
+            file: config_path,
+            line: line_number,
+            section: if in_section { Some(&section) } else { None },
+            key,
+        };
+
         if !in_section {
-            println!("Error parsing {}:{}", config_path, line_number);
-            faulty = true;
+            state.errors.push(pos.error(
+                ConfigErrorKind::NoSection,
+                &format!("Error parsing {}:{}", config_path, line_number),
+            ));
             continue;
         }
 
-        // env_assign is a special case as the key names are not known at compile time so do not fit in the match
-
-        if key.starts_with("env_assign.") {
-            let period_pos = key.find('.');
-            let env_name = key[period_pos.unwrap() + 1..].trim();
-            if !value.is_empty() {
-                if opt.clone().env_assign.is_none() {
-                    opt.env_assign = Some(HashMap::new());
-                }
-                opt.env_assign
-                    .as_mut()
-                    .unwrap()
-                    .entry(env_name.to_string())
-                    .or_insert_with(|| value.to_string());
-            }
+        if let Some(alias) = section.strip_prefix("alias:") {
+            ini_alias(alias, key, value, ro, &pos, state);
             continue;
         }
 
         match key {
             "include" => {
                 if !value.starts_with('/') {
-                    println!("Includes should start with /");
-                    return true;
+                    let mut error =
+                        pos.error(ConfigErrorKind::Include, "Includes should start with /");
+                    error.fatal = true;
+                    state.errors.push(error);
+                    return;
                 }
-                if read_ini_config_file(value, vec_eo, ro, fail_error, bytes, ini_list) {
-                    println!("Could not include file");
-                    return true;
+                if !parse_ini_file(value, vec_eo, ro, state) {
+                    let mut error = pos.error(ConfigErrorKind::Include, "Could not include file");
+                    error.fatal = true;
+                    state.errors.push(error);
+                    return;
                 }
-                continue;
             }
             "includedir" => {
                 if !value.starts_with('/') {
-                    println!("Includes should start with /");
-                    return true;
+                    let mut error =
+                        pos.error(ConfigErrorKind::Include, "Includes should start with /");
+                    error.fatal = true;
+                    state.errors.push(error);
+                    return;
                 }
                 match fs::read_dir(value) {
-                    Err(_x) => {
-                        faulty = true;
+                    Err(x) => {
+                        state.errors.push(pos.error(
+                            ConfigErrorKind::Include,
+                            &format!("Could not read directory {}: {}", value, x),
+                        ));
                     }
                     Ok(inc) => {
+                        // others who may write the directory could remove files from it
+                        let owners = config_owners(&state.admin_uids);
+                        if let Some(fault) = config_path_fault(value, &owners) {
+                            let mut error = pos.error(ConfigErrorKind::Permission, &fault);
+                            error.fatal = true;
+                            state.errors.push(error);
+                            return;
+                        }
+                        state.sources.push(CacheKey::new(value));
                         let mut collect = vec![];
                         for file in inc {
                             collect.push(file.unwrap().path().to_str().unwrap().to_string());
@@ -577,306 +963,2231 @@ pub fn read_ini(
                             if !can_dir_include(&file) {
                                 continue;
                             }
-                            if read_ini_config_file(&file, vec_eo, ro, fail_error, bytes, ini_list)
-                            {
-                                println!("Could not include file");
-                                return true;
+                            if !parse_ini_file(&file, vec_eo, ro, state) {
+                                let mut error =
+                                    pos.error(ConfigErrorKind::Include, "Could not include file");
+                                error.fatal = true;
+                                state.errors.push(error);
+                                return;
                             }
                         }
                     }
                 }
-
-                continue;
-            }
-            "name" => {
-                opt.name = Some(value.to_string());
-                opt.configured = true;
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
-                }
-            }
-            "exact_name" => {
-                opt.exact_name = Some(value.to_string());
-                opt.configured = true;
-            }
-            "hostname" => {
-                opt.hostname = Some(value.to_string());
-                opt.configured = true;
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
-                }
-            }
-            "exact_hostname" => {
-                opt.exact_hostname = Some(value.to_string());
-                opt.configured = true;
             }
-            "target" => {
-                opt.target = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
+            "admin_uid" => {
+                if state.file_uid != 0 {
+                    let mut error = pos.error(
+                        ConfigErrorKind::Permission,
+                        &format!(
+                            "admin_uid may only be set in a file owned by root, {}:{}",
+                            config_path, line_number
+                        ),
+                    );
+                    error.fatal = true;
+                    state.errors.push(error);
+                    return;
                 }
-            }
-            "exact_target" => {
-                opt.exact_target = Some(value.to_string());
-            }
-            "target_group" => {
-                opt.target_group = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
+                for admin in value.split(',') {
+                    match admin_uid(admin.trim()) {
+                        Some(uid) => state.admin_uids.push(uid),
+                        None => state.errors.push(pos.error(
+                            ConfigErrorKind::BadValue,
+                            &format!("Could not find admin_uid {}", admin.trim()),
+                        )),
+                    }
                 }
             }
-            "exact_target_group" => {
-                opt.exact_target_group = Some(value.to_string());
-            }
-            "permit" => opt.permit = Some(value == "true"),
-            "require_pass" => opt.require_pass = Some(value != "false"),
-            "type" => match value.to_lowercase().as_str() {
-                "edit" => opt.acl_type = Acltype::Edit,
-                "list" => opt.acl_type = Acltype::List,
-                _ => opt.acl_type = Acltype::Run,
-            },
-            "group" => opt.group = value == "true",
-            "regex" | "rule" => {
-                opt.rule = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
+            "require_signature" => {
+                if state.file_uid != 0 {
+                    let mut error = pos.error(
+                        ConfigErrorKind::Permission,
+                        &format!(
+                            "require_signature may only be set in a file owned by root, {}:{}",
+                            config_path, line_number
+                        ),
+                    );
+                    error.fatal = true;
+                    state.errors.push(error);
+                    return;
                 }
-            }
-            "exact_regex" | "exact_rule" => {
-                opt.exact_rule = Some(value.to_string());
-                opt.configured = true;
-            }
-            "notbefore" if value.len() == 8 => {
-                opt.notbefore = Some(
-                    parse_date_from_str(value, "%Y%m%d")
-                        .unwrap()
-                        .and_hms_opt(0, 0, 0)
-                        .unwrap(),
-                )
-            }
-            "notafter" if value.len() == 8 => {
-                opt.notafter = Some(
-                    parse_date_from_str(value, "%Y%m%d")
-                        .unwrap()
-                        .and_hms_opt(23, 59, 59)
-                        .unwrap(),
-                )
-            }
-            "notbefore" if value.len() == 14 => {
-                opt.notbefore = Some(parse_datetime_from_str(value, "%Y%m%d%H%M%S").unwrap())
-            }
-            "notafter" if value.len() == 14 => {
-                opt.notafter = Some(parse_datetime_from_str(value, "%Y%m%d%H%M%S").unwrap())
-            }
-            "datematch" => {
-                opt.datematch = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
+
+                let keyring_dir = match value {
+                    "true" => default_keyring_dir(),
+                    _ if value.starts_with('/') => value.to_string(),
+                    _ => {
+                        let mut error = pos.error(
+                            ConfigErrorKind::BadValue,
+                            "require_signature should be true or a keyring directory starting with /",
+                        );
+                        error.fatal = true;
+                        state.errors.push(error);
+                        return;
+                    }
+                };
+
+                // a file read once signatures are required has already been verified
+                let verified = state.keyring.is_some();
+
+                // files read before this were not verified, so signatures must be required first
+                if !verified {
+                    let mut unsigned: Vec<&String> = state
+                        .ini_list
+                        .keys()
+                        .filter(|file| *file != config_path)
+                        .collect();
+                    if !unsigned.is_empty() {
+                        unsigned.sort();
+                        let mut error = pos.error(
+                            ConfigErrorKind::Signature,
+                            &format!(
+                                "require_signature must come before any include, {} has been read unsigned",
+                                unsigned
+                                    .iter()
+                                    .map(|f| f.as_str())
+                                    .collect::<Vec<&str>>()
+                                    .join(", ")
+                            ),
+                        );
+                        error.fatal = true;
+                        state.errors.push(error);
+                        return;
+                    }
                 }
-            }
-            "dir" => {
-                opt.dir = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
-                {
-                    faulty = true;
+
+                let keyring_source = CacheKey::new(&keyring_dir);
+                match read_keyring(&keyring_dir) {
+                    Ok(keys) => {
+                        state.sources.push(keyring_source);
+                        state.sources.extend(keys.iter().map(|k| k.source.clone()));
+                        state.keyring = Some(keys);
+                    }
+                    Err(message) => {
+                        let mut error = pos.error(ConfigErrorKind::Signature, &message);
+                        error.fatal = true;
+                        state.errors.push(error);
+                        return;
+                    }
                 }
-            }
-            "exact_dir" => {
-                opt.exact_dir = Some(value.to_string());
-                if fail_error
-                    && regex_build(value, ro, config_path, &section, Some(line_number as i32))
-                        .is_none()
+
+                // the file that asks for signatures must be signed too
+                if !verified
+                    && config_path != "static"
+                    && !verify_config_signature(config_path, conf, state)
                 {
-                    faulty = true;
+                    return;
                 }
             }
-            "permit_env" => {
-                if !value.is_empty() {
-                    opt.env_permit = Some(value.to_string());
-                }
-            }
-            "exitcmd" => {
-                if !value.is_empty() {
-                    opt.exitcmd = Some(value.to_string());
+            "inherit" => match state.sections.get(value) {
+                Some(parent) => {
+                    // the parent is the base, keys already given in this section are applied again on top
+                    let mut base = parent.clone();
+                    base.section.clone_from(&section);
+                    base.file_name = String::from(config_path);
+                    base.line = opt.line;
+                    base.inherit = Some(value.to_string());
+                    for (_, key, _) in &section_keys {
+                        forget_inherited_env(&mut base, key);
+                    }
+
+                    let mut replay_errors = vec![];
+                    for (line, key, value) in &section_keys {
+                        let pos = IniPosition {
+                            file: config_path,
+                            line: *line,
+                            section: Some(&section),
+                            key,
+                        };
+                        ini_set(
+                            &mut base,
+                            key,
+                            value,
+                            ro,
+                            &pos,
+                            &state.aliases,
+                            &mut replay_errors,
+                        );
+                    }
+                    opt = base;
                 }
-            }
-            "editmode" => {
-                if !value.is_empty() {
-                    if value.parse::<i16>().is_ok() {
-                        opt.edit_mode = Some(EditMode::Mode(
-                            i32::from_str_radix(value.trim_start_matches('0'), 8)
-                                .expect("unable to parse editmode"),
+                None => {
+                    if value == section {
+                        state.errors.push(pos.error(
+                            ConfigErrorKind::InheritLoop,
+                            &format!("Section {} cannot inherit from itself", section),
                         ));
-                    } else if value.to_lowercase() == "keep" {
-                        opt.edit_mode = Some(EditMode::Keep(true));
                     } else {
-                        println!("Could not convert {} to numerical file mode", value);
-                        faulty = true;
+                        state.errors.push(pos.error(
+                            ConfigErrorKind::Inherit,
+                            &format!(
+                                "Cannot inherit {} as it is not defined before {}:{}",
+                                value, config_path, line_number
+                            ),
+                        ));
                     }
                 }
-            }
-            "reason" => {
-                if value == "true" || value == "false" {
-                    opt.reason = Some(ReasonType::Need(value == "true"));
-                } else {
-                    opt.reason = Some(ReasonType::Text(value.to_string()));
-                }
-            }
-            "last" => opt.last = Some(value == "true"),
-            "syslog" => opt.syslog = Some(value == "true"),
-            "timeout" => {
-                let timeout: Result<u32, core::num::ParseIntError> = value.parse();
-                if fail_error && timeout.is_err() {
-                    faulty = true;
-                } else {
-                    opt.timeout = Some(timeout.unwrap());
-                }
-            }
-            "search_path" => {
-                opt.search_path = Some(value.to_string());
-            }
-            "token_timeout" => {
-                let token_timeout: Result<u64, core::num::ParseIntError> = value.parse();
-                if fail_error && token_timeout.is_err() {
-                    faulty = true;
-                } else {
-                    opt.token_timeout = Some(token_timeout.unwrap());
+            },
+            _ => {
+                if opt.inherit.is_some() && !section_keys.iter().any(|(_, k, _)| k == key) {
+                    forget_inherited_env(&mut opt, key);
                 }
-            }
-            &_ => {
-                println!("Error parsing {}:{}", config_path, line_number);
-                faulty = true;
+                ini_set(
+                    &mut opt,
+                    key,
+                    value,
+                    ro,
+                    &pos,
+                    &state.aliases,
+                    &mut state.errors,
+                );
+                section_keys.push((line_number, key.to_string(), value.to_string()));
             }
         }
     }
 
-    if opt.configured {
-        vec_eo.push(opt);
+    if in_section {
+        end_section(opt, &section_keys, vec_eo, ro, state);
     }
 
-    fail_error && faulty
+    state.ini_list.insert(config_path.to_string(), false);
 }
 
-/// read through an ini config file, appending EnvOptions to vec_eo
-/// hardcoded limit of 10M for confs
-pub fn read_ini_config_file(
-    config_path: &str,
-    vec_eo: &mut Vec<EnvOptions>,
-    ro: &RunOptions,
-    fail_error: bool,
-    bytes: &mut u64,
-    ini_list: &mut HashMap<String, bool>,
-) -> bool {
-    let path = Path::new(config_path);
-    let display = path.display();
+/// drop a parent's env_assign.X that an inheriting section gives, as the first env_assign.X in a
+/// section is kept but the section's own should replace the parent's
+fn forget_inherited_env(opt: &mut EnvOptions, key: &str) {
+    if let (Some(name), Some(env_assign)) = (key.strip_prefix("env_assign."), &mut opt.env_assign) {
+        env_assign.remove(name.trim());
+    }
+}
 
-    let file = match File::open(path) {
-        Err(why) => {
-            println!("Could not open {}: {}", display, why);
-            return true;
-        }
-        Ok(file) => file,
+/// set timezone, notbefore and notafter from the last of each key in the section. dates without
+/// an offset are in the section timezone, or that of an earlier default section, otherwise UTC.
+/// values that ini_set found faulty are skipped as they have been reported already
+fn ini_dates(opt: &mut EnvOptions, section_keys: &[(usize, String, String)], state: &mut IniState) {
+    let file = opt.file_name.clone();
+    let section = opt.section.clone();
+    let errors = &mut state.errors;
+    let mut date_error = |line: usize, key: &str, kind: ConfigErrorKind, message: String| {
+        let pos = IniPosition {
+            file: &file,
+            line,
+            section: Some(&section),
+            key,
+        };
+        errors.push(pos.error(kind, &message));
+    };
+    let last = |name: &str| {
+        section_keys
+            .iter()
+            .rev()
+            .find(|(_, key, _)| key == name)
+            .map(|(line, key, value)| (*line, key.as_str(), value.as_str()))
     };
 
-    match nix::sys::stat::fstat(file.as_raw_fd()) {
-        Err(why) => {
-            println!("Could not stat {}: {}", display, why);
-            return true;
+    if let Some((_, _, value)) = last("timezone") {
+        if value.parse::<Tz>().is_err() {
+            return;
         }
-        Ok(stat_data) => {
-            if stat_data.st_mode & libc::S_IFREG != libc::S_IFREG {
-                println!("Refusing to open non-regular file");
-                return true;
-            }
+        opt.timezone = Some(value.to_string());
+    } else if opt.timezone.is_none() {
+        opt.timezone.clone_from(&state.timezone);
+    }
 
-            if (stat_data.st_mode & !libc::S_IFMT) & (0o022) != 0 {
-                println!("Refusing to parse file as group or other write permission bits are set");
-                return true;
-            }
+    let tz = opt
+        .timezone
+        .as_ref()
+        .and_then(|tz| tz.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC);
+
+    let checked = |value: &str| parse_config_date(value, &Tz::UTC, false).is_ok();
+
+    if let Some((line, key, value)) = last("notbefore").filter(|(_, _, v)| checked(v)) {
+        match parse_config_date(value, &tz, false) {
+            Ok(date) => opt.notbefore = Some(date),
+            Err(x) => date_error(
+                line,
+                key,
+                ConfigErrorKind::BadDate,
+                format!("Could not parse date {}: {}", value, x),
+            ),
         }
     }
 
-    let byte_limit = 1024 * 1024 * 10;
-
-    if *bytes >= byte_limit {
-        println!("Exiting as too much config has already been read.");
-        std::process::exit(1);
+    if let Some((line, key, value)) = last("notafter") {
+        let date = match value.strip_prefix('+') {
+            Some(relative) => match (opt.notbefore, parse_duration(relative)) {
+                (Some(notbefore), Some(duration)) => Ok(notbefore + duration),
+                (None, Some(_)) => Err("a relative notafter needs notbefore".to_string()),
+                (_, None) => return,
+            },
+            None if !checked(value) => return,
+            None => parse_config_date(value, &tz, true),
+        };
+        match date {
+            Ok(date) => opt.notafter = Some(date),
+            Err(x) => date_error(
+                line,
+                key,
+                ConfigErrorKind::BadDate,
+                format!("Could not parse date {}: {}", value, x),
+            ),
+        }
     }
+}
 
-    let mut s = String::new();
-    let reader = BufReader::new(file).take(byte_limit).read_to_string(&mut s);
-
-    match reader {
-        Ok(n) => {
-            *bytes += s.as_bytes().len() as u64;
-            if n >= byte_limit as usize {
-                println!("Exiting as too much config has already been read.");
-                std::process::exit(1);
-            }
+/// set exclude_dates from the exclude_dates_file of the section, or that of an earlier default
+/// section unless the section gives false
+fn ini_exclude_dates(
+    opt: &mut EnvOptions,
+    section_keys: &[(usize, String, String)],
+    state: &mut IniState,
+) {
+    let key = section_keys
+        .iter()
+        .rev()
+        .find(|(_, key, _)| key == "exclude_dates_file");
+
+    match key {
+        Some((_, _, value)) if value == "false" => opt.exclude_dates_file = None,
+        Some((_, _, value)) if value.starts_with('/') => {
+            opt.exclude_dates_file = Some(value.clone())
         }
-        Err(why) => {
-            println!("Could not read {}: {}", display, why);
-            return true;
+        Some(_) => return,
+        None if opt.exclude_dates_file.is_none() => {
+            opt.exclude_dates_file.clone_from(&state.exclude_dates_file)
         }
+        None => {}
     }
 
-    read_ini(&s, vec_eo, ro, fail_error, config_path, bytes, ini_list)
-}
+    if key.is_some() && opt.section.starts_with("default") {
+        state.exclude_dates_file.clone_from(&opt.exclude_dates_file);
+    }
 
-pub fn read_ini_config_str(
-    config: &str,
-    vec_eo: &mut Vec<EnvOptions>,
-    ro: &RunOptions,
-    fail_error: bool,
-    bytes: &mut u64,
-    ini_list: &mut HashMap<String, bool>,
-) -> bool {
-    read_ini(config, vec_eo, ro, fail_error, "static", bytes, ini_list)
-}
+    let file = match &opt.exclude_dates_file {
+        Some(file) => file.clone(),
+        None => {
+            opt.exclude_dates = None;
+            return;
+        }
+    };
+    let timezone = opt.timezone.clone().unwrap_or_else(|| "UTC".to_string());
+    let cache_key = (file.clone(), timezone.clone());
+
+    if !state.exclude_dates.contains_key(&cache_key) {
+        let tz = timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+        let owners = config_owners(&state.admin_uids);
+        state.sources.push(CacheKey::new(&file));
+        let ranges = match read_exclude_dates(&file, &tz, &owners) {
+            Ok(ranges) => Some(ranges),
+            Err(mut error) => {
+                // a problem with the file itself is reported against the section that names it
+                if error.line.is_none() {
+                    if let Some((line, key, _)) = key {
+                        error.file.clone_from(&opt.file_name);
+                        error.line = Some(*line);
+                        error.section = Some(opt.section.clone());
+                        error.key = Some(key.clone());
+                    }
+                }
+                state.errors.push(error);
+                None
+            }
+        };
+        state.exclude_dates.insert(cache_key.clone(), ranges);
+    }
 
-/// may we execute with this hostname
-pub fn hostname_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool {
-    if item.exact_hostname.is_some() {
-        let hostname = item.exact_hostname.as_ref().unwrap();
+    opt.exclude_dates = state.exclude_dates[&cache_key].clone();
+}
 
-        if hostname != &ro.hostname
-            && hostname.ne(&"any".to_string())
-            && hostname.ne(&"localhost".to_string())
-        {
-            // println!("{}: hostname mismatch: {}", item.section, hostname);
-            return false;
-        }
-        return true;
+/// read a root owned file of dates, one per line, or a start and end date separated by white
+/// space. # starts a comment. ranges are returned as UTC in order, with overlaps joined
+fn read_exclude_dates(file: &str, tz: &Tz, owners: &[u32]) -> Result<Vec<DateRange>, ConfigError> {
+    if let Some(fault) = config_path_fault(file, owners) {
+        return Err(ConfigError::new(ConfigErrorKind::Permission, file, &fault));
     }
 
-    if item.hostname.is_some() {
-        let hostname_re = match regex_build(
-            item.hostname.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(why) => {
+            return Err(ConfigError::new(
+                ConfigErrorKind::Io,
+                file,
+                &format!("Could not read {}: {}", file, why),
+            ));
+        }
+    };
+
+    let mut ranges = vec![];
+    for (mut line_number, line) in text.lines().enumerate() {
+        line_number += 1;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut dates = line.split_whitespace();
+        let start = dates.next().unwrap();
+        let end = dates.next().unwrap_or(start);
+        let range = match (
+            parse_config_date(start, tz, false),
+            parse_config_date(end, tz, true),
+            dates.next(),
         ) {
+            (Ok(start), Ok(end), None) if start <= end => Ok((start, end)),
+            (Ok(_), Ok(_), None) => Err("the end is before the start".to_string()),
+            (Err(x), _, _) | (_, Err(x), _) => Err(x),
+            (_, _, Some(extra)) => Err(format!("unexpected {}", extra)),
+        };
+
+        match range {
+            Ok(range) => ranges.push(range),
+            Err(x) => {
+                let mut error = ConfigError::new(
+                    ConfigErrorKind::BadDate,
+                    file,
+                    &format!("Could not parse dates {}: {}", line, x),
+                );
+                error.line = Some(line_number);
+                return Err(error);
+            }
+        }
+    }
+
+    ranges.sort();
+    let mut joined: Vec<DateRange> = vec![];
+    for (start, end) in ranges {
+        match joined.last_mut() {
+            Some(last) if start <= last.1 + Duration::seconds(1) => last.1 = last.1.max(end),
+            _ => joined.push((start, end)),
+        }
+    }
+    Ok(joined)
+}
+
+/// return the end of the excluded range that date falls in
+pub fn excluded_until(item: &EnvOptions, date: &NaiveDateTime) -> Option<NaiveDateTime> {
+    item.exclude_dates
+        .as_ref()?
+        .iter()
+        .find(|(start, end)| start <= date && date <= end)
+        .map(|(_, end)| *end)
+}
+
+/// read a duration such as 90s, 30m, 8h, 2d or 1w
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if value.len() < 2 {
+        return None;
+    }
+    let (count, unit) = value.split_at(value.len() - 1);
+    let count = count.parse::<i64>().ok()?;
+    match unit {
+        "s" => Duration::try_seconds(count),
+        "m" => Duration::try_minutes(count),
+        "h" => Duration::try_hours(count),
+        "d" => Duration::try_days(count),
+        "w" => Duration::try_weeks(count),
+        _ => None,
+    }
+}
+
+/// read a YYYYmmdd, YYYYmmddHHMMSS or ISO-8601 date, returning it as UTC. dates without an offset
+/// are in tz, those without a time start the day, or end it when end_of_day is set
+pub fn parse_config_date(value: &str, tz: &Tz, end_of_day: bool) -> Result<NaiveDateTime, String> {
+    let day_time = |date: NaiveDate| {
+        if end_of_day {
+            date.and_hms_opt(23, 59, 59)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        }
+    };
+    let utc = |naive: NaiveDateTime| match tz.from_local_datetime(&naive).earliest() {
+        Some(date) => Ok(date.naive_utc()),
+        None => Err(format!("{} does not exist in {}", naive, tz)),
+    };
+
+    if value.bytes().all(|b| b.is_ascii_digit()) {
+        return match value.len() {
+            8 => match NaiveDate::parse_from_str(value, "%Y%m%d") {
+                Ok(date) => utc(day_time(date).unwrap()),
+                Err(x) => Err(x.to_string()),
+            },
+            14 => match NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S") {
+                Ok(date) => utc(date),
+                Err(x) => Err(x.to_string()),
+            },
+            _ => Err("expected YYYYmmdd, YYYYmmddHHMMSS or an ISO-8601 date".to_string()),
+        };
+    }
+
+    let offset_value = match value.strip_suffix('Z') {
+        Some(v) => format!("{}+00:00", v),
+        None => value.to_string(),
+    };
+    for format in ["%Y-%m-%dT%H:%M:%S%#z", "%Y-%m-%dT%H:%M%#z"] {
+        if let Ok(date) = DateTime::parse_from_str(&offset_value, format) {
+            return Ok(date.naive_utc());
+        }
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return utc(date);
+        }
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => utc(day_time(date).unwrap()),
+        Err(_) => Err("expected YYYYmmdd, YYYYmmddHHMMSS or an ISO-8601 date".to_string()),
+    }
+}
+
+/// days and times of day that a section may match within, times are minutes from midnight and a
+/// range that ends before it starts carries on into the next day
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleWindow {
+    pub days: [bool; 7],
+    pub times: Vec<(u32, u32)>,
+    pub timezone: Option<Tz>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub windows: Vec<ScheduleWindow>,
+}
+
+const SCHEDULE_DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// read Mon-Fri or Sat,Sun, where ranges may wrap such as Fri-Mon
+fn parse_schedule_days(value: &str) -> Option<[bool; 7]> {
+    let day = |name: &str| {
+        SCHEDULE_DAYS
+            .iter()
+            .position(|d| d.eq_ignore_ascii_case(name))
+    };
+    let mut days = [false; 7];
+
+    for part in value.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (mut from, to) = (day(from)?, day(to)?);
+                days[from] = true;
+                while from != to {
+                    from = (from + 1) % 7;
+                    days[from] = true;
+                }
+            }
+            None if part == "*" => days = [true; 7],
+            None => days[day(part)?] = true,
+        }
+    }
+    Some(days)
+}
+
+/// read HH:MM as minutes from midnight, 24:00 is the end of the day
+fn parse_schedule_time(value: &str) -> Option<u32> {
+    let (hour, minute) = value.split_once(':')?;
+    if hour.len() != 2 || minute.len() != 2 {
+        return None;
+    }
+    let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    match (hour, minute) {
+        (24, 0) => Some(24 * 60),
+        (0..=23, 0..=59) => Some(hour * 60 + minute),
+        _ => None,
+    }
+}
+
+/// read a schedule such as Mon-Fri 08:00-18:00 Europe/London, windows are separated by ; and each
+/// has optional days, comma separated time ranges and a time zone, in that order
+pub fn parse_schedule(value: &str) -> Result<Schedule, String> {
+    let mut windows = vec![];
+
+    for text in value.split(';') {
+        let mut window = ScheduleWindow {
+            days: [true; 7],
+            times: vec![],
+            timezone: None,
+        };
+        let mut tokens = text.split_whitespace().peekable();
+        if tokens.peek().is_none() {
+            return Err("empty schedule window".to_string());
+        }
+
+        if let Some(days) = tokens.peek().and_then(|t| parse_schedule_days(t)) {
+            window.days = days;
+            tokens.next();
+        }
+
+        if tokens
+            .peek()
+            .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            for range in tokens.next().unwrap().split(',') {
+                let times = range.split_once('-').and_then(|(start, end)| {
+                    Some((parse_schedule_time(start)?, parse_schedule_time(end)?))
+                });
+                match times {
+                    Some((start, end)) if start != end && start < 24 * 60 => {
+                        window.times.push((start, end))
+                    }
+                    _ => return Err(format!("expected HH:MM-HH:MM, not {}", range)),
+                }
+            }
+        }
+
+        if let Some(zone) = tokens.next() {
+            match zone.parse::<Tz>() {
+                Ok(tz) => window.timezone = Some(tz),
+                Err(_) => return Err(format!("unknown day, time or timezone {}", zone)),
+            }
+        }
+
+        if let Some(extra) = tokens.next() {
+            return Err(format!("unexpected {} after the timezone", extra));
+        }
+        windows.push(window);
+    }
+    Ok(Schedule { windows })
+}
+
+impl Schedule {
+    /// true if the UTC date falls in a window, which are in tz unless they name their own
+    pub fn contains(&self, date: &NaiveDateTime, tz: &Tz) -> bool {
+        self.windows.iter().any(|window| {
+            let local = Utc
+                .from_utc_datetime(date)
+                .with_timezone(&window.timezone.unwrap_or(*tz));
+            let day = local.weekday().num_days_from_monday() as usize;
+            let minute = local.hour() * 60 + local.minute();
+
+            if window.times.is_empty() {
+                return window.days[day];
+            }
+
+            window.times.iter().any(|&(start, end)| {
+                if start < end {
+                    window.days[day] && minute >= start && minute < end
+                } else {
+                    (window.days[day] && minute >= start)
+                        || (window.days[(day + 6) % 7] && minute < end)
+                }
+            })
+        })
+    }
+}
+
+/// replace %{alias:NAME} with the members of NAME, the name is returned if it is not defined
+pub fn alias_expand(v: &str, aliases: &HashMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = v;
+
+    while let Some(start) = rest.find("%{alias:") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 8..];
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => return Err(after.to_string()),
+        };
+
+        match aliases.get(&after[..end]) {
+            Some(members) => expanded.push_str(&format!("(?:{})", members)),
+            None => return Err(after[..end].to_string()),
+        }
+        rest = &after[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// define an alias from the members of an [alias:NAME] section
+fn ini_alias(
+    alias: &str,
+    key: &str,
+    value: &str,
+    ro: &RunOptions,
+    pos: &IniPosition,
+    state: &mut IniState,
+) {
+    if key != "members" {
+        state.errors.push(pos.error(
+            ConfigErrorKind::UnknownKey,
+            &format!("Error parsing {}:{}", pos.file, pos.line),
+        ));
+        return;
+    }
+
+    match alias_expand(value, &state.aliases) {
+        Ok(members) => {
+            if regex_compile(&members, ro).is_err() {
+                state.errors.push(pos.error(
+                    ConfigErrorKind::BadRegex,
+                    &format!("Error parsing {}: alias:{}:{}", pos.file, alias, pos.line),
+                ));
+                return;
+            }
+            state.aliases.insert(alias.to_string(), members);
+        }
+        Err(name) if name == alias => {
+            state.errors.push(pos.error(
+                ConfigErrorKind::AliasLoop,
+                &format!("Alias {} cannot refer to itself", alias),
+            ));
+        }
+        Err(name) => {
+            state.errors.push(pos.error(
+                ConfigErrorKind::Alias,
+                &format!(
+                    "Alias {} is not defined before {}:{}",
+                    name, pos.file, pos.line
+                ),
+            ));
+        }
+    }
+}
+
+/// apply a single key and value to a section
+fn ini_set(
+    opt: &mut EnvOptions,
+    key: &str,
+    value: &str,
+    ro: &RunOptions,
+    pos: &IniPosition,
+    aliases: &HashMap<String, String>,
+    errors: &mut Vec<ConfigError>,
+) {
+    let expanded;
+    let value = match key {
+        "name" | "hostname" | "target" | "target_group" | "regex" | "rule" => {
+            match alias_expand(value, aliases) {
+                Ok(v) => {
+                    expanded = v;
+                    expanded.as_str()
+                }
+                Err(name) => {
+                    errors.push(pos.error(
+                        ConfigErrorKind::Alias,
+                        &format!(
+                            "Alias {} is not defined before {}:{}",
+                            name, pos.file, pos.line
+                        ),
+                    ));
+                    return;
+                }
+            }
+        }
+        _ => value,
+    };
+
+    // env_assign is a special case as the key names are not known at compile time so do not fit in the match
+
+    if key.starts_with("env_assign.") {
+        let period_pos = key.find('.');
+        let env_name = key[period_pos.unwrap() + 1..].trim();
+        if !value.is_empty() {
+            if opt.clone().env_assign.is_none() {
+                opt.env_assign = Some(HashMap::new());
+            }
+            opt.env_assign
+                .as_mut()
+                .unwrap()
+                .entry(env_name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        return;
+    }
+
+    // rlimit.X is a special case for the same reason, X being the resource name
+    if let Some(resource) = key.strip_prefix("rlimit.") {
+        match parse_rlimit(resource, value) {
+            Ok(limit) => {
+                opt.rlimit
+                    .get_or_insert_with(BTreeMap::new)
+                    .insert(resource.to_string(), limit);
+            }
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse rlimit.{} {}: {}", resource, value, x),
+            )),
+        }
+        return;
+    }
+
+    // argN is a special case for the same reason, arg0 is the command and arg1 its first argument
+    if let Some(n) = key.strip_prefix("arg") {
+        if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+            match (n.parse::<usize>(), regex_compile(value, ro)) {
+                (Ok(n), Ok(re)) => {
+                    opt.regex_cache
+                        .get_or_insert_with(|| RegexCache::new(ro))
+                        .patterns
+                        .insert(value.to_string(), re);
+                    opt.args
+                        .get_or_insert_with(BTreeMap::new)
+                        .insert(n, value.to_string());
+                }
+                _ => {
+                    errors.push(pos.error(
+                        ConfigErrorKind::BadRegex,
+                        &format!(
+                            "Error parsing {}: {}:{}",
+                            pos.file,
+                            pos.section.unwrap_or_default(),
+                            pos.line
+                        ),
+                    ));
+                }
+            }
+            return;
+        }
+    }
+
+    match key {
+        "name" | "hostname" | "target" | "target_group" | "regex" | "rule" | "datematch"
+        | "dir" | "exact_dir" | "permit_env" | "args_rest" => match regex_compile(value, ro) {
+            Ok(re) => {
+                if key != "exact_dir" {
+                    opt.regex_cache
+                        .get_or_insert_with(|| RegexCache::new(ro))
+                        .patterns
+                        .insert(value.to_string(), re);
+                }
+            }
+            Err(_) => {
+                errors.push(pos.error(
+                    ConfigErrorKind::BadRegex,
+                    &format!(
+                        "Error parsing {}: {}:{}",
+                        pos.file,
+                        pos.section.unwrap_or_default(),
+                        pos.line
+                    ),
+                ));
+            }
+        },
+        _ => {}
+    }
+
+    match key {
+        "name" => {
+            opt.name = Some(value.to_string());
+            opt.configured = true;
+        }
+        "exact_name" => {
+            opt.exact_name = Some(value.to_string());
+            opt.configured = true;
+        }
+        "hostname" => {
+            opt.hostname = Some(value.to_string());
+            opt.configured = true;
+        }
+        "exact_hostname" => {
+            opt.exact_hostname = Some(value.to_string());
+            opt.configured = true;
+        }
+        "target" => {
+            opt.target = Some(value.to_string());
+        }
+        "exact_target" => {
+            opt.exact_target = Some(value.to_string());
+        }
+        "target_group" => {
+            opt.target_group = Some(value.to_string());
+        }
+        "exact_target_group" => {
+            opt.exact_target_group = Some(value.to_string());
+        }
+        "permit" => opt.permit = Some(value == "true"),
+        "require_pass" => opt.require_pass = Some(value != "false"),
+        "type" => match value.to_lowercase().as_str() {
+            "edit" => opt.acl_type = Acltype::Edit,
+            "list" => opt.acl_type = Acltype::List,
+            _ => opt.acl_type = Acltype::Run,
+        },
+        "group" => opt.group = value == "true",
+        "regex" | "rule" => {
+            opt.rule = Some(value.to_string());
+        }
+        "exact_regex" | "exact_rule" => {
+            opt.exact_rule = Some(value.to_string());
+            opt.configured = true;
+        }
+        "args_max" => match value.parse::<usize>() {
+            Ok(args_max) => opt.args_max = Some(args_max),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse args_max {}: {}", value, x),
+            )),
+        },
+        "args_rest" => {
+            opt.args_rest = Some(value.to_string());
+        }
+        // these depend on each other, so are only checked here and set by ini_dates when the section ends
+        "timezone" => {
+            if value.parse::<Tz>().is_err() {
+                errors.push(pos.error(
+                    ConfigErrorKind::BadValue,
+                    &format!("Unknown timezone {}", value),
+                ));
+            }
+        }
+        "notbefore" | "notafter" => {
+            let checked = match value.strip_prefix('+') {
+                Some(relative) if key == "notafter" => match parse_duration(relative) {
+                    Some(_) => Ok(()),
+                    None => Err("expected a number followed by s, m, h, d or w".to_string()),
+                },
+                _ => parse_config_date(value, &Tz::UTC, false).map(|_| ()),
+            };
+            if let Err(x) = checked {
+                errors.push(pos.error(
+                    ConfigErrorKind::BadDate,
+                    &format!("Could not parse date {}: {}", value, x),
+                ));
+            }
+        }
+        "datematch" => {
+            opt.datematch = Some(value.to_string());
+        }
+        // read by ini_exclude_dates when the section ends, as dates are in the section timezone
+        "exclude_dates_file" => {
+            if value != "false" && !value.starts_with('/') {
+                errors.push(pos.error(
+                    ConfigErrorKind::BadValue,
+                    "exclude_dates_file should start with / or be false",
+                ));
+            }
+        }
+        "schedule" => match parse_schedule(value) {
+            Ok(schedule) => {
+                opt.schedule = Some(value.to_string());
+                opt.schedule_cache = Some(schedule);
+            }
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse schedule {}: {}", value, x),
+            )),
+        },
+        "dir" => {
+            opt.dir = Some(value.to_string());
+        }
+        "exact_dir" => {
+            opt.exact_dir = Some(value.to_string());
+        }
+        "permit_env" => {
+            if !value.is_empty() {
+                opt.env_permit = Some(value.to_string());
+            }
+        }
+        "exitcmd" => {
+            if !value.is_empty() {
+                opt.exitcmd = Some(value.to_string());
+            }
+        }
+        "editmode" => {
+            if !value.is_empty() {
+                if value.to_lowercase() == "keep" {
+                    opt.edit_mode = Some(EditMode::Keep(true));
+                } else {
+                    match i32::from_str_radix(value, 8) {
+                        Ok(mode) if value.parse::<i16>().is_ok() => {
+                            opt.edit_mode = Some(EditMode::Mode(mode))
+                        }
+                        _ => errors.push(pos.error(
+                            ConfigErrorKind::BadValue,
+                            &format!("Could not convert {} to numerical file mode", value),
+                        )),
+                    }
+                }
+            }
+        }
+        "reason" => {
+            if value == "true" || value == "false" {
+                opt.reason = Some(ReasonType::Need(value == "true"));
+            } else {
+                opt.reason = Some(ReasonType::Text(value.to_string()));
+            }
+        }
+        "last" => opt.last = Some(value == "true"),
+        "syslog" => opt.syslog = Some(value == "true"),
+        "timeout" => match value.parse::<u32>() {
+            Ok(timeout) => opt.timeout = Some(timeout),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse timeout {}: {}", value, x),
+            )),
+        },
+        "search_path" => {
+            opt.search_path = Some(value.to_string());
+        }
+        "chroot" => {
+            if value.starts_with('/') {
+                opt.chroot = Some(value.to_string());
+            } else {
+                errors.push(pos.error(
+                    ConfigErrorKind::BadValue,
+                    &format!("Could not parse chroot {}: should start with /", value),
+                ));
+            }
+        }
+        "private_tmp" => opt.private_tmp = Some(value == "true"),
+        "use_pty" => opt.use_pty = Some(value == "true"),
+        "record" => opt.record = Some(value == "true"),
+        "supervise" => opt.supervise = Some(value == "true"),
+        "readonly_paths" | "hide_paths" => match parse_paths(value) {
+            Ok(paths) if key == "readonly_paths" => opt.readonly_paths = Some(paths),
+            Ok(paths) => opt.hide_paths = Some(paths),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse {} {}: {}", key, value, x),
+            )),
+        },
+        "capabilities" => match parse_capabilities(value) {
+            Ok(capabilities) => opt.capabilities = Some(capabilities),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse capabilities {}: {}", value, x),
+            )),
+        },
+        "allow_unsafe_binary" => opt.allow_unsafe_binary = Some(value == "true"),
+        "resolve_links" => opt.resolve_links = Some(value == "true"),
+        "noexec" => opt.noexec = Some(value == "true"),
+        "nice" => match value.parse::<i32>() {
+            Ok(nice) if (-20..=19).contains(&nice) => opt.nice = Some(nice),
+            Ok(_) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse nice {}: expected -20 to 19", value),
+            )),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse nice {}: {}", value, x),
+            )),
+        },
+        "ioprio" => match parse_ioprio(value) {
+            Ok(_) => opt.ioprio = Some(value.to_string()),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse ioprio {}: {}", value, x),
+            )),
+        },
+        "digest" => match parse_digests(value) {
+            Ok(digests) => opt.digest = Some(digests),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse digest {}: {}", value, x),
+            )),
+        },
+        "token_timeout" => match value.parse::<u64>() {
+            Ok(token_timeout) => opt.token_timeout = Some(token_timeout),
+            Err(x) => errors.push(pos.error(
+                ConfigErrorKind::BadValue,
+                &format!("Could not parse token_timeout {}: {}", value, x),
+            )),
+        },
+        &_ => {
+            errors.push(pos.error(
+                ConfigErrorKind::UnknownKey,
+                &format!("Error parsing {}:{}", pos.file, pos.line),
+            ));
+        }
+    }
+}
+
+/// read through an ini config file, appending EnvOptions to vec_eo
+/// hardcoded limit of 10M for confs
+pub fn read_ini_config_file(
+    config_path: &str,
+    vec_eo: &mut Vec<EnvOptions>,
+    ro: &RunOptions,
+    fail_error: bool,
+    bytes: &mut u64,
+    ini_list: &mut HashMap<String, bool>,
+) -> bool {
+    let mut state = IniState::new(bytes, ini_list);
+    parse_ini_file(config_path, vec_eo, ro, &mut state);
+    print_config_faults(&state.errors, fail_error)
+}
+
+/// open and parse a config file, return false if the file could not be read at all
+fn parse_ini_file(
+    config_path: &str,
+    vec_eo: &mut Vec<EnvOptions>,
+    ro: &RunOptions,
+    state: &mut IniState,
+) -> bool {
+    let path = Path::new(config_path);
+    let display = path.display();
+
+    let errors = &mut state.errors;
+    let mut file_error = |kind: ConfigErrorKind, message: &str| {
+        let mut error = ConfigError::new(kind, config_path, message);
+        error.fatal = true;
+        errors.push(error);
+        false
+    };
+
+    let file = match File::open(path) {
+        Err(why) => {
+            return file_error(
+                ConfigErrorKind::Io,
+                &format!("Could not open {}: {}", display, why),
+            );
+        }
+        Ok(file) => file,
+    };
+
+    // the file as it was read, rather than whatever is at the path by the time policy is cached
+    let (file_uid, source) = match nix::sys::stat::fstat(file.as_raw_fd()) {
+        Err(why) => {
+            return file_error(
+                ConfigErrorKind::Io,
+                &format!("Could not stat {}: {}", display, why),
+            );
+        }
+        Ok(stat_data) => {
+            if stat_data.st_mode & libc::S_IFREG != libc::S_IFREG {
+                return file_error(
+                    ConfigErrorKind::Permission,
+                    "Refusing to open non-regular file",
+                );
+            }
+
+            if (stat_data.st_mode & !libc::S_IFMT) & (0o022) != 0 {
+                return file_error(
+                    ConfigErrorKind::Permission,
+                    "Refusing to parse file as group or other write permission bits are set",
+                );
+            }
+
+            (
+                stat_data.st_uid,
+                CacheKey::from_stat(config_path, &stat_data),
+            )
+        }
+    };
+
+    if let Some(fault) = config_path_fault(config_path, &config_owners(&state.admin_uids)) {
+        return file_error(ConfigErrorKind::Permission, &fault);
+    }
+
+    let byte_limit = 1024 * 1024 * 10;
+
+    if *state.bytes >= byte_limit {
+        return file_error(
+            ConfigErrorKind::TooLarge,
+            "Too much config has already been read",
+        );
+    }
+
+    let mut s = String::new();
+    let reader = BufReader::new(file).take(byte_limit).read_to_string(&mut s);
+
+    match reader {
+        Ok(n) => {
+            *state.bytes += s.as_bytes().len() as u64;
+            if n >= byte_limit as usize {
+                return file_error(
+                    ConfigErrorKind::TooLarge,
+                    "Too much config has already been read",
+                );
+            }
+        }
+        Err(why) => {
+            return file_error(
+                ConfigErrorKind::Io,
+                &format!("Could not read {}: {}", display, why),
+            );
+        }
+    }
+
+    state.sources.push(Some(source));
+
+    if state.keyring.is_some() && !verify_config_signature(config_path, &s, state) {
+        return false;
+    }
+
+    let parent_uid = state.file_uid;
+    state.file_uid = file_uid;
+    parse_ini(&s, vec_eo, ro, config_path, state);
+    state.file_uid = parent_uid;
+    true
+}
+
+/// return the directory that require_signature=true reads public keys from
+pub fn default_keyring_dir() -> String {
+    "/etc/please.keys".to_string()
+}
+
+/// a signify public key from the keyring
+#[derive(Clone, Debug)]
+pub struct SignifyKey {
+    pub file: String,
+    pub keynum: [u8; 8],
+    key: ed25519_dalek::VerifyingKey,
+    source: Option<CacheKey>,
+}
+
+/// the outcome of checking the signature of a config file
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SignatureState {
+    Good,
+    Unsigned,
+    UnknownKey,
+    Bad,
+}
+
+#[derive(Clone, Debug)]
+pub struct SignatureStatus {
+    pub file: String,
+    pub state: SignatureState,
+    pub keynum: Option<[u8; 8]>,
+    pub message: String,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+fn keynum_hex(keynum: &[u8; 8]) -> String {
+    keynum.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// decode the base64 line of a signify key or signature file, which follows an untrusted comment
+fn signify_decode(text: &str, len: usize) -> Option<Vec<u8>> {
+    use base64::Engine;
+
+    let mut lines = text.lines();
+    if !lines.next()?.starts_with("untrusted comment: ") {
+        return None;
+    }
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(lines.next()?.trim())
+        .ok()?;
+    if data.len() != len || &data[0..2] != b"Ed" {
+        return None;
+    }
+    Some(data)
+}
+
+/// read the signify public keys, *.pub, from a root owned keyring directory
+pub fn read_keyring(dir: &str) -> Result<Vec<SignifyKey>, String> {
+    // keys are trusted more than config, so admin_uid may not own them
+    let owners = config_owners(&[]);
+    if let Some(fault) = config_path_fault(dir, &owners) {
+        return Err(fault);
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) => return Err(format!("Could not read keyring {}: {}", dir, why)),
+    };
+
+    let mut files = vec![];
+    for entry in entries.flatten() {
+        let file = entry.path().to_string_lossy().to_string();
+        if file.ends_with(".pub") {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    let mut keys = vec![];
+    for file in files {
+        if let Some(fault) = config_path_fault(&file, &owners) {
+            return Err(fault);
+        }
+        let source = CacheKey::new(&file);
+        let key = fs::read_to_string(&file)
+            .ok()
+            .and_then(|text| signify_decode(&text, 42))
+            .and_then(|data| {
+                let key: [u8; 32] = data[10..42].try_into().ok()?;
+                Some(SignifyKey {
+                    file: file.clone(),
+                    keynum: data[2..10].try_into().ok()?,
+                    key: ed25519_dalek::VerifyingKey::from_bytes(&key).ok()?,
+                    source: source.clone(),
+                })
+            });
+        match key {
+            Some(key) => keys.push(key),
+            None => return Err(format!("Could not read public key {}", file)),
+        }
+    }
+
+    if keys.is_empty() {
+        return Err(format!("No public keys found in keyring {}", dir));
+    }
+    Ok(keys)
+}
+
+/// check data read from file against the detached signature in file.sig
+pub fn verify_signature(file: &str, data: &[u8], keys: &[SignifyKey]) -> SignatureStatus {
+    let sig_file = format!("{}.sig", file);
+    let status =
+        |state: SignatureState, keynum: Option<[u8; 8]>, message: String| SignatureStatus {
+            file: file.to_string(),
+            state,
+            keynum,
+            message,
+        };
+
+    let text = match fs::read_to_string(&sig_file) {
+        Ok(text) => text,
+        Err(why) => {
+            return status(
+                SignatureState::Unsigned,
+                None,
+                format!("unsigned, could not read {}: {}", sig_file, why),
+            );
+        }
+    };
+
+    let data_sig = match signify_decode(&text, 74) {
+        Some(data_sig) => data_sig,
+        None => {
+            return status(
+                SignatureState::Bad,
+                None,
+                format!("bad signature, could not decode {}", sig_file),
+            );
+        }
+    };
+
+    let keynum: [u8; 8] = data_sig[2..10].try_into().unwrap();
+    let key = match keys.iter().find(|k| k.keynum == keynum) {
+        Some(key) => key,
+        None => {
+            return status(
+                SignatureState::UnknownKey,
+                Some(keynum),
+                format!("signed by unknown key {}", keynum_hex(&keynum)),
+            );
+        }
+    };
+
+    let signature = ed25519_dalek::Signature::from_slice(&data_sig[10..74]);
+    match signature.map(|sig| key.key.verify_strict(data, &sig)) {
+        Ok(Ok(())) => status(
+            SignatureState::Good,
+            Some(keynum),
+            format!(
+                "good signature by key {} from {}",
+                keynum_hex(&keynum),
+                key.file
+            ),
+        ),
+        _ => status(
+            SignatureState::Bad,
+            Some(keynum),
+            format!(
+                "bad signature for key {} from {}",
+                keynum_hex(&keynum),
+                key.file
+            ),
+        ),
+    }
+}
+
+/// verify a config file read while require_signature is set, adding a fatal error if it is not good
+fn verify_config_signature(config_path: &str, data: &str, state: &mut IniState) -> bool {
+    state
+        .sources
+        .push(CacheKey::new(&format!("{}.sig", config_path)));
+    let status = verify_signature(
+        config_path,
+        data.as_bytes(),
+        state.keyring.as_ref().unwrap(),
+    );
+    let good = status.state == SignatureState::Good;
+
+    if !good {
+        let mut error = ConfigError::new(
+            ConfigErrorKind::Signature,
+            config_path,
+            &format!(
+                "Refusing to parse {} as it is {}",
+                config_path, status.message
+            ),
+        );
+        error.fatal = true;
+        state.errors.push(error);
+    }
+    state.signatures.push(status);
+    good
+}
+
+/// return the uid of an admin_uid value, which may be a number or a user name
+fn admin_uid(value: &str) -> Option<u32> {
+    match value.parse::<u32>() {
+        Ok(uid) => Some(uid),
+        Err(_) => get_user_by_name(value).map(|user| user.uid()),
+    }
+}
+
+/// return the uids that may own config, root, the effective user reading it and any admin_uid
+fn config_owners(admin_uids: &[u32]) -> Vec<u32> {
+    let mut owners = vec![0, nix::unistd::geteuid().as_raw()];
+    owners.extend_from_slice(admin_uids);
+    owners
+}
+
+/// walk each component of path from /, returning a message naming the first that is not owned
+/// by one of owners or that group or other may write. sticky directories such as /tmp may be
+/// writable by others as they cannot replace what they do not own
+pub fn config_path_fault(path: &str, owners: &[u32]) -> Option<String> {
+    path_fault(path, Path::new(path), owners, "parse")
+}
+
+/// the first part of the path to a binary that a user other than root could replace, both
+/// where it was found and, if that is a link, where it leads
+pub fn binary_fault(binary: &str) -> Option<String> {
+    let path = Path::new(binary);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Some(fault) = path_fault(binary, parent, &[0], "run") {
+            return Some(fault);
+        }
+    }
+    path_fault(binary, path, &[0], "run")
+}
+
+/// walk path for config_path_fault or binary_fault, naming the file as display
+fn path_fault(display: &str, path: &Path, owners: &[u32], action: &str) -> Option<String> {
+    let canonical = match fs::canonicalize(path) {
+        Ok(canonical) => canonical,
+        Err(why) => return Some(format!("Could not resolve {}: {}", path.display(), why)),
+    };
+
+    let mut components: Vec<&Path> = canonical.ancestors().collect();
+    components.reverse();
+
+    for component in components {
+        let stat_data = match nix::sys::stat::stat(component) {
+            Ok(stat_data) => stat_data,
+            Err(why) => {
+                return Some(format!("Could not stat {}: {}", component.display(), why));
+            }
+        };
+
+        if !owners.contains(&stat_data.st_uid) {
+            return Some(format!(
+                "Refusing to {} {} as {} is owned by uid {}",
+                action,
+                display,
+                component.display(),
+                stat_data.st_uid
+            ));
+        }
+
+        let sticky_dir = stat_data.st_mode & libc::S_IFMT == libc::S_IFDIR
+            && stat_data.st_mode & libc::S_ISVTX != 0;
+        if stat_data.st_mode & 0o022 != 0 && !sticky_dir {
+            return Some(format!(
+                "Refusing to {} {} as {} is writable by group or other",
+                action,
+                display,
+                component.display()
+            ));
+        }
+    }
+    None
+}
+
+pub fn read_ini_config_str(
+    config: &str,
+    vec_eo: &mut Vec<EnvOptions>,
+    ro: &RunOptions,
+    fail_error: bool,
+    bytes: &mut u64,
+    ini_list: &mut HashMap<String, bool>,
+) -> bool {
+    read_ini(config, vec_eo, ro, fail_error, "static", bytes, ini_list)
+}
+
+/// read a config file and its includes, returning every section or every problem found
+pub fn parse_ini_config_file(
+    config_path: &str,
+    ro: &RunOptions,
+) -> Result<Vec<EnvOptions>, Vec<ConfigError>> {
+    parse_ini_config_sources(config_path, ro).parsed
+}
+
+/// config as read, with the files and include directories it was read from
+struct ConfigSources {
+    parsed: Result<Vec<EnvOptions>, Vec<ConfigError>>,
+    sources: Vec<Option<CacheKey>>,
+    admin_uids: Vec<u32>,
+    signatures: Vec<SignatureStatus>,
+}
+
+/// as parse_ini_config_file, also returning the signature of each file when require_signature is set
+pub fn parse_ini_config_file_signatures(
+    config_path: &str,
+    ro: &RunOptions,
+) -> (
+    Result<Vec<EnvOptions>, Vec<ConfigError>>,
+    Vec<SignatureStatus>,
+) {
+    let config = parse_ini_config_sources(config_path, ro);
+    (config.parsed, config.signatures)
+}
+
+/// as parse_ini_config_file, also returning the files and include directories that were read
+fn parse_ini_config_sources(config_path: &str, ro: &RunOptions) -> ConfigSources {
+    let mut vec_eo = vec![];
+    let mut bytes = 0;
+    let mut ini_list = HashMap::new();
+    let mut state = IniState::new(&mut bytes, &mut ini_list);
+
+    parse_ini_file(config_path, &mut vec_eo, ro, &mut state);
+
+    let errors = std::mem::take(&mut state.errors);
+    let admin_uids = std::mem::take(&mut state.admin_uids);
+    let signatures = std::mem::take(&mut state.signatures);
+    let sources = std::mem::take(&mut state.sources);
+
+    ConfigSources {
+        parsed: if errors.is_empty() {
+            Ok(vec_eo)
+        } else {
+            Err(errors)
+        },
+        sources,
+        admin_uids,
+        signatures,
+    }
+}
+
+/// a file or directory that cached policy was read from, as it was when read
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+struct CacheKey {
+    path: String,
+    dev: u64,
+    ino: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+    ctime: i64,
+    ctime_nsec: i64,
+    size: i64,
+}
+
+impl CacheKey {
+    fn new(path: &str) -> Option<CacheKey> {
+        let stat_data = nix::sys::stat::stat(path).ok()?;
+        Some(CacheKey::from_stat(path, &stat_data))
+    }
+
+    // the types of stat fields differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    fn from_stat(path: &str, stat_data: &nix::sys::stat::FileStat) -> CacheKey {
+        CacheKey {
+            path: path.to_string(),
+            dev: stat_data.st_dev as u64,
+            ino: stat_data.st_ino as u64,
+            mtime: stat_data.st_mtime as i64,
+            mtime_nsec: stat_data.st_mtime_nsec as i64,
+            // chmod and chown only change ctime
+            ctime: stat_data.st_ctime as i64,
+            ctime_nsec: stat_data.st_ctime_nsec as i64,
+            size: stat_data.st_size as i64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PolicyCache {
+    version: String,
+    admin_uids: Vec<u32>,
+    keys: Vec<CacheKey>,
+    sections: Vec<EnvOptions>,
+}
+
+/// return the directory that parsed policy is cached in
+pub fn cache_dir() -> String {
+    "/var/run/please/cache".to_string()
+}
+
+/// return the path of the cache for a config file
+pub fn cache_path(dir: &str, config_path: &str) -> String {
+    format!("{}/{}.cache", dir, config_path.replace('/', "_"))
+}
+
+/// true if path is a root owned directory or file that only root may write
+fn cache_path_safe(stat_data: &nix::sys::stat::FileStat) -> bool {
+    stat_data.st_uid == 0 && (stat_data.st_mode & !libc::S_IFMT) & 0o022 == 0
+}
+
+/// read cached policy for config_path, None if it is missing, unsafe or anything it was read from has changed
+pub fn load_policy_cache(dir: &str, config_path: &str, ro: &RunOptions) -> Option<Vec<EnvOptions>> {
+    match nix::sys::stat::stat(dir) {
+        Ok(stat_data) if cache_path_safe(&stat_data) => {}
+        _ => return None,
+    }
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(cache_path(dir, config_path))
+        .ok()?;
+    let stat_data = nix::sys::stat::fstat(file.as_raw_fd()).ok()?;
+    if stat_data.st_mode & libc::S_IFMT != libc::S_IFREG
+        || !cache_path_safe(&stat_data)
+        || (stat_data.st_mode & 0o077) != 0
+    {
+        return None;
+    }
+
+    let mut data = vec![];
+    if file.take(MAX_CACHE_BYTES).read_to_end(&mut data).is_err() {
+        return None;
+    }
+
+    let cache: PolicyCache = postcard::from_bytes(&data).ok()?;
+    if cache.version != env!("CARGO_PKG_VERSION") {
+        return None;
+    }
+
+    // parent directories are not keyed, so their ownership and modes are walked again
+    let owners = config_owners(&cache.admin_uids);
+    for key in &cache.keys {
+        if CacheKey::new(&key.path).as_ref() != Some(key)
+            || config_path_fault(&key.path, &owners).is_some()
+        {
+            return None;
+        }
+    }
+
+    let mut vec_eo = cache.sections;
+    for opt in vec_eo.iter_mut() {
+        compile_section_defaults(opt, ro);
+        opt.schedule_cache = opt.schedule.as_deref().and_then(|s| parse_schedule(s).ok());
+    }
+    Some(vec_eo)
+}
+
+/// write policy read from sources so that later runs can skip parsing, false if it could not be written
+fn save_policy_cache(
+    dir: &str,
+    config_path: &str,
+    vec_eo: &[EnvOptions],
+    sources: &[Option<CacheKey>],
+    admin_uids: &[u32],
+) -> bool {
+    let keys: Vec<CacheKey> = match sources.iter().cloned().collect() {
+        Some(keys) => keys,
+        None => return false,
+    };
+
+    let cache = PolicyCache {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        admin_uids: admin_uids.to_vec(),
+        keys,
+        sections: vec_eo.to_vec(),
+    };
+    let data = match postcard::to_stdvec(&cache) {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+
+    if !Path::new(dir).is_dir()
+        && fs::DirBuilder::new()
+            .mode(0o700)
+            .recursive(true)
+            .create(dir)
+            .is_err()
+    {
+        return false;
+    }
+
+    let path = cache_path(dir, config_path);
+    let tmp_path = format!("{}.{}", path, prng_alpha_num_string(8));
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .and_then(|mut file| file.write_all(&data));
+
+    if written.is_err() || fs::rename(&tmp_path, &path).is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return false;
+    }
+    true
+}
+
+/// as parse_ini_config_file, using policy cached in dir when nothing it was read from has changed
+pub fn parse_ini_config_file_cached(
+    config_path: &str,
+    ro: &RunOptions,
+    dir: &str,
+) -> Result<Vec<EnvOptions>, Vec<ConfigError>> {
+    if let Some(vec_eo) = load_policy_cache(dir, config_path, ro) {
+        return Ok(vec_eo);
+    }
+
+    let config = parse_ini_config_sources(config_path, ro);
+    if let Ok(vec_eo) = &config.parsed {
+        save_policy_cache(
+            dir,
+            config_path,
+            vec_eo,
+            &config.sources,
+            &config.admin_uids,
+        );
+    }
+    config.parsed
+}
+
+/// as parse_ini_config_file, but for config held in a string
+pub fn parse_ini_config_str(
+    config: &str,
+    ro: &RunOptions,
+) -> Result<Vec<EnvOptions>, Vec<ConfigError>> {
+    let mut vec_eo = vec![];
+    let mut bytes = 0;
+    let mut ini_list = HashMap::new();
+    let mut state = IniState::new(&mut bytes, &mut ini_list);
+
+    parse_ini(config, &mut vec_eo, ro, "static", &mut state);
+
+    if state.errors.is_empty() {
+        Ok(vec_eo)
+    } else {
+        Err(state.errors)
+    }
+}
+
+/// how much a lint finding matters
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum LintSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LintSeverity::Info => write!(f, "info"),
+            LintSeverity::Warning => write!(f, "warning"),
+            LintSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// a questionable section found by lint_config
+#[derive(Clone, Debug)]
+pub struct LintFinding {
+    pub severity: LintSeverity,
+    pub file: String,
+    pub line: Option<usize>,
+    pub section: String,
+    pub message: String,
+}
+
+impl LintFinding {
+    fn new(severity: LintSeverity, item: &EnvOptions, message: &str) -> LintFinding {
+        LintFinding {
+            severity,
+            file: item.file_name.clone(),
+            line: item.line,
+            section: item.section.clone(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: [{}] {}",
+            section_location(&self.file, self.line),
+            self.severity,
+            self.section,
+            self.message
+        )
+    }
+}
+
+/// programs that can start a shell or run other programs from within
+pub const SHELL_ESCAPES: [&str; 46] = [
+    "sh",
+    "bash",
+    "dash",
+    "zsh",
+    "ksh",
+    "csh",
+    "awk",
+    "busybox",
+    "ed",
+    "emacs",
+    "env",
+    "ex",
+    "expect",
+    "find",
+    "ftp",
+    "gawk",
+    "gdb",
+    "git",
+    "irb",
+    "journalctl",
+    "less",
+    "lua",
+    "make",
+    "man",
+    "mawk",
+    "more",
+    "nano",
+    "nawk",
+    "nmap",
+    "node",
+    "nvim",
+    "perl",
+    "php",
+    "python",
+    "python2",
+    "python3",
+    "rsync",
+    "ruby",
+    "screen",
+    "ssh",
+    "tar",
+    "tclsh",
+    "tmux",
+    "vi",
+    "view",
+    "vim",
+];
+
+/// file:line, or just file when the line is not known
+fn section_location(file: &str, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", file, line),
+        None => file.to_string(),
+    }
+}
+
+/// true if the pattern matches anything, ignoring anchors
+fn lint_wildcard(pattern: &str) -> bool {
+    matches!(
+        pattern.trim_start_matches('^').trim_end_matches('$'),
+        ".*" | "(.*)" | "(?:.*)" | ".*?"
+    )
+}
+
+/// true if anything matched by b's exact/regex pair is also matched by a's
+fn lint_field_covers(
+    a: &EnvOptions,
+    a_exact: &Option<String>,
+    a_re: &Option<String>,
+    b_exact: &Option<String>,
+    b_re: &Option<String>,
+    ro: &RunOptions,
+) -> bool {
+    if a_exact.is_some() {
+        return b_exact == a_exact;
+    }
+
+    match a_re {
+        None => b_exact.is_none() && b_re.is_none(),
+        Some(pattern) => {
+            if lint_wildcard(pattern) || b_re.as_ref() == Some(pattern) {
+                return true;
+            }
+            // a literal pattern can be tested as if it were exact
+            let literal = match (b_exact, b_re) {
+                (Some(exact), _) => exact,
+                (None, Some(b_re)) if &regex::escape(b_re) == b_re => b_re,
+                _ => return false,
+            };
+            section_regex(a, pattern, ro, None).is_some_and(|re| re.is_match(literal))
+        }
+    }
+}
+
+/// true if a and b agree on the keys that section_failures matches on but lint_covers does not
+/// model. every field is named so that a new key must be placed here before this builds
+fn lint_unmodelled_equal(a: &EnvOptions, b: &EnvOptions) -> bool {
+    let EnvOptions {
+        // modelled by lint_covers
+        name: _,
+        exact_name: _,
+        group: _,
+        rule: _,
+        exact_rule: _,
+        args: _,
+        args_max: _,
+        args_rest: _,
+        notbefore: _,
+        notafter: _,
+        datematch: _,
+        schedule: _,
+        exclude_dates_file: _,
+        exclude_dates: _,
+        timezone: _,
+        target: _,
+        exact_target: _,
+        target_group: _,
+        exact_target_group: _,
+        hostname: _,
+        exact_hostname: _,
+        dir: _,
+        exact_dir: _,
+        env_permit: _,
+        digest: _,
+        acl_type: _,
+        regex_cache: _,
+        schedule_cache: _,
+        // used once a section has matched, not to match it
+        permit: _,
+        require_pass: _,
+        last: _,
+        file_name: _,
+        section: _,
+        line: _,
+        configured: _,
+        inherit: _,
+        exitcmd: _,
+        edit_mode: _,
+        reason: _,
+        syslog: _,
+        env_assign: _,
+        timeout: _,
+        token_timeout: _,
+        allow_unsafe_binary: _,
+        noexec: _,
+        rlimit: _,
+        nice: _,
+        ioprio: _,
+        chroot: _,
+        private_tmp: _,
+        readonly_paths: _,
+        hide_paths: _,
+        capabilities: _,
+        use_pty: _,
+        record: _,
+        supervise: _,
+        // matched on, but not modelled
+        search_path,
+        resolve_links,
+    } = a;
+
+    search_path == &b.search_path && resolve_links == &b.resolve_links
+}
+
+/// true if every request matched by b would also be matched by a
+fn lint_covers(a: &EnvOptions, b: &EnvOptions, ro: &RunOptions) -> bool {
+    if a.acl_type != b.acl_type || !lint_unmodelled_equal(a, b) {
+        return false;
+    }
+
+    if a.notbefore.is_some() && (b.notbefore.is_none() || a.notbefore > b.notbefore) {
+        return false;
+    }
+    if a.notafter.is_some() && (b.notafter.is_none() || a.notafter < b.notafter) {
+        return false;
+    }
+    if a.datematch.is_some() && (a.datematch != b.datematch || a.timezone != b.timezone) {
+        return false;
+    }
+    if a.schedule.is_some() && (a.schedule != b.schedule || a.timezone != b.timezone) {
+        return false;
+    }
+    if a.exclude_dates_file.is_some()
+        && (a.exclude_dates_file != b.exclude_dates_file || a.exclude_dates != b.exclude_dates)
+    {
+        return false;
+    }
+    // a pinned binary does not cover one with any other digest
+    if let Some(digests) = &a.digest {
+        if !b
+            .digest
+            .as_ref()
+            .is_some_and(|b| b.iter().all(|d| digests.contains(d)))
+        {
+            return false;
+        }
+    }
+
+    let any_name = a.exact_name.is_none() && a.name.as_ref().is_some_and(|n| lint_wildcard(n));
+    if a.group != b.group && (a.group || !any_name) {
+        return false;
+    }
+    if !lint_field_covers(a, &a.exact_name, &a.name, &b.exact_name, &b.name, ro) {
+        return false;
+    }
+
+    // hostname_ok lets any and localhost through on every host
+    let any_host = match (&a.exact_hostname, &a.hostname) {
+        (Some(exact), _) => exact == "any" || exact == "localhost",
+        (None, None) => true,
+        (None, Some(pattern)) => match section_regex(a, pattern, ro, None) {
+            Some(re) => lint_wildcard(pattern) || re.is_match("any") || re.is_match("localhost"),
+            None => false,
+        },
+    };
+    if !any_host
+        && !lint_field_covers(
+            a,
+            &a.exact_hostname,
+            &a.hostname,
+            &b.exact_hostname,
+            &b.hostname,
+            ro,
+        )
+    {
+        return false;
+    }
+
+    if !lint_field_covers(a, &a.exact_dir, &a.dir, &b.exact_dir, &b.dir, ro)
+        || !lint_field_covers(a, &None, &a.env_permit, &None, &b.env_permit, ro)
+        || !lint_field_covers(
+            a,
+            &a.exact_target,
+            &a.target,
+            &b.exact_target,
+            &b.target,
+            ro,
+        )
+        || !lint_field_covers(
+            a,
+            &a.exact_target_group,
+            &a.target_group,
+            &b.exact_target_group,
+            &b.target_group,
+            ro,
+        )
+    {
+        return false;
+    }
+
+    a.acl_type == Acltype::List
+        || (!a.has_args()
+            && lint_field_covers(a, &a.exact_rule, &a.rule, &b.exact_rule, &b.rule, ro))
+}
+
+/// true if the section's target matches root
+fn lint_targets_root(item: &EnvOptions, ro: &RunOptions) -> bool {
+    match (&item.exact_target, &item.target) {
+        (Some(exact), _) => exact == "root",
+        (None, Some(pattern)) => {
+            section_regex(item, pattern, ro, None).is_some_and(|re| re.is_match("root"))
+        }
+        (None, None) => false,
+    }
+}
+
+/// the program a rule runs, from the leading path of the pattern
+fn lint_rule_program(rule: &str) -> String {
+    let path: String = rule
+        .trim_start_matches('^')
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || "/._-".contains(*c))
+        .collect();
+    path.rsplit('/').next().unwrap_or("").to_string()
+}
+
+/// the first shell escape program a run section permits
+fn lint_shell_escape(item: &EnvOptions, ro: &RunOptions) -> Option<&'static str> {
+    if let Some(exact) = &item.exact_rule {
+        let program = exact.split_whitespace().next().unwrap_or("");
+        let program = program.rsplit('/').next().unwrap_or("");
+        return SHELL_ESCAPES.iter().copied().find(|b| *b == program);
+    }
+
+    // arg0 is matched against the program alone, so is narrower than the rule
+    let pattern = match item.args.as_ref().and_then(|args| args.get(&0)) {
+        Some(arg0) => arg0,
+        None => item.rule.as_ref()?,
+    };
+    let program = lint_rule_program(pattern);
+    if let Some(found) = SHELL_ESCAPES.iter().copied().find(|b| *b == program) {
+        return Some(found);
+    }
+
+    let re = section_regex(item, pattern, ro, None)?;
+    for program in SHELL_ESCAPES.iter() {
+        for dir in ["/bin", "/usr/bin", "/usr/local/bin", "/sbin", "/usr/sbin"] {
+            if re.is_match(&format!("{}/{}", dir, program)) {
+                return Some(program);
+            }
+        }
+    }
+    None
+}
+
+/// warnings that please -c reports without --lint, such as date ranges that never begin
+pub fn config_warnings(vec_eo: &[EnvOptions]) -> Vec<LintFinding> {
+    let mut findings = vec![];
+
+    for item in vec_eo {
+        if let (Some(notbefore), Some(notafter)) = (item.notbefore, item.notafter) {
+            if notbefore > notafter {
+                findings.push(LintFinding::new(
+                    LintSeverity::Warning,
+                    item,
+                    &format!(
+                        "notbefore {} UTC is later than notafter {} UTC, the section can never match",
+                        notbefore, notafter
+                    ),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+/// look for sections that cannot match, are overridden or that grant more than expected
+pub fn lint_config(vec_eo: &[EnvOptions], ro: &RunOptions) -> Vec<LintFinding> {
+    let mut findings = vec![];
+
+    for (pos, item) in vec_eo.iter().enumerate() {
+        let shadow = vec_eo[..pos]
+            .iter()
+            .find(|earlier| earlier.last == Some(true) && lint_covers(earlier, item, ro));
+        if let Some(earlier) = shadow {
+            findings.push(LintFinding::new(
+                LintSeverity::Warning,
+                item,
+                &format!(
+                    "can never match, [{}] at {} has last=true and matches first",
+                    earlier.section,
+                    section_location(&earlier.file_name, earlier.line)
+                ),
+            ));
+            continue;
+        }
+
+        // a default with permit=false is merged into later sections that do not set permit
+        if item.permit == Some(false) && item.last != Some(true) {
+            let later = vec_eo[pos + 1..].iter().find(|later| {
+                later.permit != Some(false)
+                    && (!item.section.starts_with("default") || later.permit.is_some())
+                    && lint_covers(later, item, ro)
+            });
+            if let Some(later) = later {
+                findings.push(LintFinding::new(
+                    LintSeverity::Warning,
+                    item,
+                    &format!(
+                        "permit=false is overridden by [{}] at {}",
+                        later.section,
+                        section_location(&later.file_name, later.line)
+                    ),
+                ));
+            }
+        }
+
+        let mut reported_wildcard = false;
+        if item.permit() && item.acl_type != Acltype::List && lint_targets_root(item, ro) {
+            let probes = match item.acl_type {
+                Acltype::Edit => ["/etc/shadow", "/etc/please.ini"],
+                _ => ["/bin/sh", "/usr/bin/id -u"],
+            };
+            let wildcard = item.exact_rule.is_none()
+                && !item.has_args()
+                && item.rule.as_ref().is_some_and(|pattern| {
+                    section_regex(item, pattern, ro, None)
+                        .is_some_and(|re| probes.iter().all(|p| re.is_match(p)))
+                });
+            if wildcard {
+                reported_wildcard = true;
+                let severity = match item.require_pass {
+                    Some(false) => LintSeverity::Error,
+                    _ => LintSeverity::Warning,
+                };
+                findings.push(LintFinding::new(
+                    severity,
+                    item,
+                    &format!(
+                        "rule={} grants root to anything{}",
+                        item.rule.as_ref().unwrap(),
+                        if severity == LintSeverity::Error {
+                            " without a password"
+                        } else {
+                            ""
+                        }
+                    ),
+                ));
+            }
+        }
+
+        if item.permit()
+            && item.acl_type == Acltype::Run
+            && !reported_wildcard
+            && item.noexec != Some(true)
+        {
+            if let Some(program) = lint_shell_escape(item, ro) {
+                findings.push(LintFinding::new(
+                    LintSeverity::Warning,
+                    item,
+                    &format!(
+                        "{} can start a shell or other programs, consider noexec=true",
+                        program
+                    ),
+                ));
+            }
+        }
+
+        if let Some(EditMode::Mode(mode)) = item.edit_mode {
+            if mode & 0o002 != 0 {
+                findings.push(LintFinding::new(
+                    LintSeverity::Error,
+                    item,
+                    &format!("editmode={:o} makes files world writable", mode),
+                ));
+            }
+        }
+
+        if item.allow_unsafe_binary == Some(true) && item.acl_type == Acltype::Run {
+            findings.push(LintFinding::new(
+                LintSeverity::Warning,
+                item,
+                "allow_unsafe_binary=true runs binaries that other users may replace",
+            ));
+        }
+
+        if let Some(exitcmd) = &item.exitcmd {
+            let program = exitcmd.split_whitespace().next().unwrap_or("");
+            match nix::sys::stat::stat(program) {
+                Err(x) => findings.push(LintFinding::new(
+                    LintSeverity::Warning,
+                    item,
+                    &format!("exitcmd {} cannot be checked: {}", program, x),
+                )),
+                Ok(stat_data) => {
+                    if stat_data.st_uid != 0 {
+                        findings.push(LintFinding::new(
+                            LintSeverity::Error,
+                            item,
+                            &format!("exitcmd {} is not owned by root", program),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+/// may we execute with this hostname
+pub fn hostname_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool {
+    if item.exact_hostname.is_some() {
+        let hostname = item.exact_hostname.as_ref().unwrap();
+
+        if hostname != &ro.hostname
+            && hostname.ne(&"any".to_string())
+            && hostname.ne(&"localhost".to_string())
+        {
+            // println!("{}: hostname mismatch: {}", item.section, hostname);
+            return false;
+        }
+        return true;
+    }
+
+    if item.hostname.is_some() {
+        let hostname_re = match section_regex(item, item.hostname.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.hostname.as_ref().unwrap());
@@ -907,13 +3218,7 @@ pub fn target_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool
     }
 
     if item.target.is_some() {
-        let target_re = match regex_build(
-            item.target.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let target_re = match section_regex(item, item.target.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.target.as_ref().unwrap());
@@ -953,19 +3258,14 @@ pub fn target_group_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) ->
     }
 
     if item.target_group.is_some() {
-        let target_group_re = match regex_build(
-            item.target_group.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
-            Some(check) => check,
-            None => {
-                println!("Could not compile {}", &item.target_group.as_ref().unwrap());
-                return false;
-            }
-        };
+        let target_group_re =
+            match section_regex(item, item.target_group.as_ref().unwrap(), ro, line) {
+                Some(check) => check,
+                None => {
+                    println!("Could not compile {}", &item.target_group.as_ref().unwrap());
+                    return false;
+                }
+            };
 
         if target_group_re.is_match(ro.target_group.as_ref().unwrap()) {
             return true;
@@ -987,13 +3287,7 @@ pub fn rule_match(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool
     }
 
     if item.rule.is_some() {
-        let rule_re = match regex_build(
-            item.rule.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let rule_re = match section_regex(item, item.rule.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.rule.as_ref().unwrap());
@@ -1012,6 +3306,49 @@ pub fn rule_match(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool
     false
 }
 
+/// match each argument, after search_path, against argN, args_max and args_rest
+pub fn args_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool {
+    if !item.has_args() {
+        return true;
+    }
+
+    let args = match (&item.acl_type, &ro.cloned_args) {
+        (Acltype::Edit, _) => &ro.new_args[..ro.new_args.len().min(1)],
+        (_, Some(cloned_args)) => &cloned_args[..],
+        (_, None) => &ro.new_args[..],
+    };
+
+    if let Some(args_max) = item.args_max {
+        if args.len() > args_max + 1 {
+            return false;
+        }
+    }
+
+    let arg_match = |v: &str, arg: &str| match section_regex(item, v, ro, line) {
+        Some(re) => re.is_match(arg),
+        None => {
+            println!("Could not compile {}", v);
+            false
+        }
+    };
+
+    let mut rest = 1;
+    if let Some(arg_res) = &item.args {
+        for (n, v) in arg_res {
+            match args.get(*n) {
+                Some(arg) if arg_match(v, arg) => {}
+                _ => return false,
+            }
+            rest = n + 1;
+        }
+    }
+
+    if let Some(args_rest) = &item.args_rest {
+        return args.iter().skip(rest).all(|arg| arg_match(args_rest, arg));
+    }
+    true
+}
+
 /// may we execute with this directory
 pub fn directory_check_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool {
     if item.exact_dir.is_some() {
@@ -1032,13 +3369,7 @@ pub fn directory_check_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>)
             return false;
         }
 
-        let dir_re = match regex_build(
-            item.dir.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let dir_re = match section_regex(item, item.dir.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.dir.as_ref().unwrap());
@@ -1070,13 +3401,7 @@ pub fn environment_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) ->
         return false;
     }
 
-    let env_re = match regex_build(
-        item.env_permit.as_ref().unwrap(),
-        ro,
-        &item.file_name,
-        &item.section,
-        line,
-    ) {
+    let env_re = match section_regex(item, item.env_permit.as_ref().unwrap(), ro, line) {
         Some(check) => check,
         None => {
             println!("Could not compile {}", &item.env_permit.as_ref().unwrap());
@@ -1108,13 +3433,7 @@ pub fn permitted_dates_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>)
     }
 
     if item.datematch.is_some() {
-        let datematch_re = match regex_build(
-            item.datematch.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let datematch_re = match section_regex(item, item.datematch.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.datematch.as_ref().unwrap());
@@ -1122,11 +3441,44 @@ pub fn permitted_dates_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>)
             }
         };
 
-        if !datematch_re.is_match(&ro.date.format("%a %e %b %T UTC %Y").to_string()) {
+        // ro.date is UTC, datematch sees the local time when the section has a timezone
+        let date = match item.timezone.as_ref().and_then(|tz| tz.parse::<Tz>().ok()) {
+            Some(tz) => Utc
+                .from_utc_datetime(&ro.date)
+                .with_timezone(&tz)
+                .format("%a %e %b %T %Z %Y")
+                .to_string(),
+            None => ro.date.format("%a %e %b %T UTC %Y").to_string(),
+        };
+
+        if !datematch_re.is_match(&date) {
             // println!("{}: skipping as not a datematch {} vs {}", item.section, item.datematch.clone().unwrap(), &ro.date.format( "%a %e %b %T UTC %Y" ).to_string() );
             return false;
         }
     }
+
+    if excluded_until(item, &ro.date).is_some() {
+        return false;
+    }
+
+    if let Some(schedule) = &item.schedule {
+        let tz = item
+            .timezone
+            .as_ref()
+            .and_then(|tz| tz.parse::<Tz>().ok())
+            .unwrap_or(Tz::UTC);
+
+        let schedule = match &item.schedule_cache {
+            Some(cached) => Cow::Borrowed(cached),
+            None => match parse_schedule(schedule) {
+                Ok(parsed) => Cow::Owned(parsed),
+                Err(_) => return false,
+            },
+        };
+        if !schedule.contains(&ro.date, &tz) {
+            return false;
+        }
+    }
     true
 }
 
@@ -1141,13 +3493,7 @@ pub fn name_matches(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bo
     }
 
     if item.name.is_some() {
-        let name_re = match regex_build(
-            item.name.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let name_re = match section_regex(item, item.name.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.name.as_ref().unwrap());
@@ -1179,13 +3525,7 @@ pub fn group_matches(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> b
     }
 
     if item.name.is_some() {
-        let name_re = match regex_build(
-            item.name.as_ref().unwrap(),
-            ro,
-            &item.file_name,
-            &item.section,
-            line,
-        ) {
+        let name_re = match section_regex(item, item.name.as_ref().unwrap(), ro, line) {
             Some(check) => check,
             None => {
                 println!("Could not compile {}", &item.name.as_ref().unwrap());
@@ -1205,50 +3545,149 @@ pub fn group_matches(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> b
     false
 }
 
-pub fn matching(item: &EnvOptions, ro: &mut RunOptions, line_error: Option<i32>) -> bool {
-    if !permitted_dates_ok(item, ro, line_error) {
-        // println!("Didn't match permitted dates");
-        return false;
-    }
+/// a check made by matching, in the order they are made
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MatchCheck {
+    Dates,
+    Name,
+    Group,
+    Hostname,
+    Dir,
+    Env,
+    Target,
+    TargetGroup,
+    Command,
+    Digest,
+    Rule,
+    Args,
+}
 
-    if !item.group && !name_matches(item, ro, line_error) {
-        // println!("not item group, and name does not match");
-        return false;
+impl fmt::Display for MatchCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MatchCheck::Dates => write!(f, "dates"),
+            MatchCheck::Name => write!(f, "name"),
+            MatchCheck::Group => write!(f, "group"),
+            MatchCheck::Hostname => write!(f, "hostname"),
+            MatchCheck::Dir => write!(f, "dir"),
+            MatchCheck::Env => write!(f, "env"),
+            MatchCheck::Target => write!(f, "target"),
+            MatchCheck::TargetGroup => write!(f, "target_group"),
+            MatchCheck::Command => write!(f, "command"),
+            MatchCheck::Digest => write!(f, "digest"),
+            MatchCheck::Rule => write!(f, "rule"),
+            MatchCheck::Args => write!(f, "args"),
+        }
     }
+}
 
-    if item.group && !group_matches(item, ro, line_error) {
-        // println!("item group, and group does not match");
-        return false;
-    }
+/// the checks a section failed for a request, empty when it matched
+#[derive(Clone, Debug)]
+pub struct MatchTrace {
+    pub section: String,
+    pub file_name: String,
+    pub line: Option<usize>,
+    pub permit: bool,
+    pub failed: Vec<MatchCheck>,
+    /// the command and where it leads, when rules were matched against a link's destination
+    pub link: Option<(String, String)>,
+}
 
-    if !hostname_ok(item, ro, line_error) {
-        // println!("hostname does not match");
-        return false;
+impl MatchTrace {
+    pub fn new(item: &EnvOptions, failed: Vec<MatchCheck>) -> MatchTrace {
+        MatchTrace {
+            section: item.section.clone(),
+            file_name: item.file_name.clone(),
+            line: item.line,
+            permit: item.permit(),
+            failed,
+            link: None,
+        }
     }
+}
 
-    if !directory_check_ok(item, ro, line_error) {
-        // println!("directory does not match");
-        return false;
+/// the located command and the path rules matched, if the command is a link
+fn matched_link(ro: &RunOptions) -> Option<(String, String)> {
+    let resolved = &ro.cloned_args.as_ref()?[0];
+    let located = ro.located_bin.get(&ro.new_args[0])?.as_ref()?;
+    if resolved == located {
+        return None;
     }
+    Some((located.clone(), resolved.clone()))
+}
 
-    if !environment_ok(item, ro, line_error) {
-        // println!("environment does not match");
-        return false;
+impl fmt::Display for MatchTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] at {}: ",
+            self.section,
+            section_location(&self.file_name, self.line)
+        )?;
+        if self.failed.is_empty() {
+            write!(
+                f,
+                "matched{}",
+                if self.permit { "" } else { ", permit=false" }
+            )?;
+        } else {
+            let failed: Vec<String> = self.failed.iter().map(|c| c.to_string()).collect();
+            write!(f, "{} mismatch", failed.join(", "))?;
+        }
+        if let Some((located, resolved)) = &self.link {
+            write!(f, ", {} links to {}, which rules match", located, resolved)?;
+        }
+        Ok(())
     }
+}
 
-    if !target_ok(item, ro, line_error) {
-        // println!("target user does not match");
-        return false;
+fn name_or_group_ok(item: &EnvOptions, ro: &RunOptions, line: Option<i32>) -> bool {
+    if item.group {
+        group_matches(item, ro, line)
+    } else {
+        name_matches(item, ro, line)
     }
+}
 
-    if !target_group_ok(item, ro, line_error) {
-        // println!("target group does not match");
-        return false;
+/// the checks a section fails, stopping at the first unless every failure is wanted
+pub fn section_failures(
+    item: &EnvOptions,
+    ro: &mut RunOptions,
+    line_error: Option<i32>,
+    every: bool,
+) -> Vec<MatchCheck> {
+    let mut failed = vec![];
+
+    type Check = fn(&EnvOptions, &RunOptions, Option<i32>) -> bool;
+    let checks: [(MatchCheck, Check); 7] = [
+        (MatchCheck::Dates, permitted_dates_ok),
+        (
+            if item.group {
+                MatchCheck::Group
+            } else {
+                MatchCheck::Name
+            },
+            name_or_group_ok,
+        ),
+        (MatchCheck::Hostname, hostname_ok),
+        (MatchCheck::Dir, directory_check_ok),
+        (MatchCheck::Env, environment_ok),
+        (MatchCheck::Target, target_ok),
+        (MatchCheck::TargetGroup, target_group_ok),
+    ];
+
+    for (check, ok) in checks {
+        if !ok(item, ro, line_error) {
+            failed.push(check);
+            if !every {
+                return failed;
+            }
+        }
     }
 
     if item.acl_type == Acltype::List {
         // println!("{}: is list", item.section);
-        return true;
+        return failed;
     }
 
     // cloned_args and command should be reset each loop
@@ -1262,24 +3701,133 @@ pub fn matching(item: &EnvOptions, ro: &mut RunOptions, line_error: Option<i32>)
     }
     ro.command = replace_new_args(ro.new_args.clone());
 
-    if item.acl_type == Acltype::Run {
-        match search_path(ro, item) {
-            None => {
-                return false;
-            }
-            Some(x) => {
-                ro.cloned_args = Some(ro.new_args.clone());
-                ro.cloned_args.as_mut().unwrap()[0] = x;
-                ro.command = replace_new_args(ro.cloned_args.as_ref().unwrap().clone());
+    if item.acl_type == Acltype::Run {
+        match search_path(ro, item) {
+            None => {
+                failed.push(MatchCheck::Command);
+                if !every {
+                    return failed;
+                }
+            }
+            Some(x) => match match item.resolve_links {
+                Some(true) => resolve_binary(&x),
+                _ => Some(x),
+            } {
+                None => {
+                    failed.push(MatchCheck::Command);
+                    if !every {
+                        return failed;
+                    }
+                }
+                Some(resolved) => {
+                    ro.cloned_args = Some(ro.new_args.clone());
+                    ro.cloned_args.as_mut().unwrap()[0] = resolved;
+                    ro.command = replace_new_args(ro.cloned_args.as_ref().unwrap().clone());
+
+                    if !digest_ok(item, ro) {
+                        failed.push(MatchCheck::Digest);
+                        if !every {
+                            return failed;
+                        }
+                    }
+                }
+            },
+        }
+    }
+    if item.acl_type == Acltype::Edit {
+        let edit_file = vec![ro.new_args[0].clone()];
+        ro.command = replace_new_args(edit_file);
+    }
+
+    if !rule_match(item, ro, line_error) {
+        failed.push(MatchCheck::Rule);
+        if !every {
+            return failed;
+        }
+    }
+
+    if !args_ok(item, ro, line_error) {
+        failed.push(MatchCheck::Args);
+    }
+    failed
+}
+
+/// true if the section matches the request, recorded in ro.trace when it is set
+pub fn matching(item: &EnvOptions, ro: &mut RunOptions, line_error: Option<i32>) -> bool {
+    let failed = section_failures(item, ro, line_error, false);
+    let matched = failed.is_empty();
+
+    if let Some(trace) = ro.trace.as_mut() {
+        trace.push(MatchTrace::new(item, failed));
+    }
+
+    matched
+}
+
+/// what --explain may tell the user about a denied request
+#[derive(Clone, Debug)]
+pub enum Explanation {
+    NotListable,
+    NoSection,
+    Closest(MatchTrace),
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Explanation::NotListable => write!(
+                f,
+                "No explanation is available as you may not list your own rules"
+            ),
+            Explanation::NoSection => write!(f, "No section is close to this request"),
+            Explanation::Closest(trace) => write!(f, "Closest section {}", trace),
+        }
+    }
+}
+
+/// the type=list entry that lets the user list their own rules
+pub fn own_list_entry(vec_eo: &[EnvOptions], ro: &RunOptions) -> EnvOptions {
+    let mut list_ro = ro.clone();
+    list_ro.acl_type = Acltype::List;
+    list_ro.target.clone_from(&ro.name);
+    list_ro.target_group = None;
+    list_ro.directory = None;
+    list_ro.allow_env_list = None;
+    list_ro.trace = None;
+    can(vec_eo, &mut list_ro)
+}
+
+/// the section that came closest to permitting the request, from those the user could list
+pub fn explain(vec_eo: &[EnvOptions], ro: &RunOptions) -> Explanation {
+    if !own_list_entry(vec_eo, ro).permit() {
+        return Explanation::NotListable;
+    }
+
+    let mut closest: Option<MatchTrace> = None;
+    for item in vec_eo {
+        // the same sections that please -l would show
+        if item.acl_type != ro.acl_type
+            || !name_or_group_ok(item, ro, None)
+            || !hostname_ok(item, ro, None)
+        {
+            continue;
+        }
+
+        let mut check_ro = ro.clone();
+        let failed = section_failures(item, &mut check_ro, None, true);
+        if closest.is_none() || failed.len() <= closest.as_ref().unwrap().failed.len() {
+            let mut trace = MatchTrace::new(item, failed);
+            if item.acl_type == Acltype::Run {
+                trace.link = matched_link(&check_ro);
             }
+            closest = Some(trace);
         }
     }
-    if item.acl_type == Acltype::Edit {
-        let edit_file = vec![ro.new_args[0].clone()];
-        ro.command = replace_new_args(edit_file);
-    }
 
-    rule_match(item, ro, line_error)
+    match closest {
+        Some(trace) => Explanation::Closest(trace),
+        None => Explanation::NoSection,
+    }
 }
 
 pub fn merge_default(default: &EnvOptions, item: &EnvOptions) -> EnvOptions {
@@ -1345,6 +3893,78 @@ pub fn merge_default(default: &EnvOptions, item: &EnvOptions) -> EnvOptions {
         merged.token_timeout = default.token_timeout;
     }
 
+    if default.allow_unsafe_binary.is_some() && item.allow_unsafe_binary.is_none() {
+        // println!("merging allow_unsafe_binary");
+        merged.allow_unsafe_binary = default.allow_unsafe_binary;
+    }
+
+    if default.resolve_links.is_some() && item.resolve_links.is_none() {
+        merged.resolve_links = default.resolve_links;
+    }
+
+    if default.noexec.is_some() && item.noexec.is_none() {
+        // println!("merging noexec");
+        merged.noexec = default.noexec;
+    }
+
+    // limits are merged by resource, so a section may add to the defaults
+    if let Some(default_rlimit) = &default.rlimit {
+        let rlimit = merged.rlimit.get_or_insert_with(BTreeMap::new);
+        for (resource, limit) in default_rlimit {
+            rlimit.entry(resource.to_string()).or_insert(*limit);
+        }
+    }
+
+    if default.nice.is_some() && item.nice.is_none() {
+        // println!("merging nice");
+        merged.nice = default.nice;
+    }
+
+    if default.ioprio.is_some() && item.ioprio.is_none() {
+        // println!("merging ioprio");
+        merged.ioprio.clone_from(&default.ioprio);
+    }
+
+    if default.chroot.is_some() && item.chroot.is_none() {
+        // println!("merging chroot");
+        merged.chroot.clone_from(&default.chroot);
+    }
+
+    if default.private_tmp.is_some() && item.private_tmp.is_none() {
+        // println!("merging private_tmp");
+        merged.private_tmp = default.private_tmp;
+    }
+
+    if default.readonly_paths.is_some() && item.readonly_paths.is_none() {
+        // println!("merging readonly_paths");
+        merged.readonly_paths.clone_from(&default.readonly_paths);
+    }
+
+    if default.hide_paths.is_some() && item.hide_paths.is_none() {
+        // println!("merging hide_paths");
+        merged.hide_paths.clone_from(&default.hide_paths);
+    }
+
+    if default.capabilities.is_some() && item.capabilities.is_none() {
+        // println!("merging capabilities");
+        merged.capabilities.clone_from(&default.capabilities);
+    }
+
+    if default.use_pty.is_some() && item.use_pty.is_none() {
+        // println!("merging use_pty");
+        merged.use_pty = default.use_pty;
+    }
+
+    if default.record.is_some() && item.record.is_none() {
+        // println!("merging record");
+        merged.record = default.record;
+    }
+
+    if default.supervise.is_some() && item.supervise.is_none() {
+        // println!("merging supervise");
+        merged.supervise = default.supervise;
+    }
+
     merged
 }
 
@@ -1431,6 +4051,57 @@ pub fn reason_ok(item: &EnvOptions, ro: &RunOptions) -> bool {
     }
 }
 
+/// the outcome of a request evaluated by simulate
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub entry: EnvOptions,
+    pub permit: bool,
+    pub reason_ok: bool,
+    pub require_pass: bool,
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "decision: {}",
+            if self.permit { "permit" } else { "deny" }
+        )?;
+        if self.entry.section.is_empty() {
+            writeln!(f, "section: none")?;
+        } else {
+            writeln!(
+                f,
+                "section: [{}] at {}",
+                self.entry.section,
+                section_location(&self.entry.file_name, self.entry.line)
+            )?;
+        }
+        if self.entry.reason.is_some() {
+            writeln!(f, "reason: {}", if self.reason_ok { "ok" } else { "fail" })?;
+        }
+        write!(f, "require_pass: {}", self.require_pass)
+    }
+}
+
+/// evaluate a request as it would be when run, without running anything
+pub fn simulate(vec_eo: &[EnvOptions], ro: &mut RunOptions) -> Simulation {
+    let entry = can(vec_eo, ro);
+    let reason = !entry.permit() || reason_ok(&entry, ro);
+
+    Simulation {
+        permit: entry.permit() && reason,
+        reason_ok: reason,
+        require_pass: entry.require_pass(),
+        entry,
+    }
+}
+
+/// read a YYYYmmdd, YYYYmmddHHMMSS or ISO-8601 date as UTC
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    parse_config_date(value, &Tz::UTC, false).ok()
+}
+
 /// find editor for user. return /usr/bin/vi if EDITOR and VISUAL are unset
 pub fn get_editor() -> String {
     let editor = "/usr/bin/vi";
@@ -1590,11 +4261,100 @@ pub fn list_rule(eo: &EnvOptions) -> String {
         return format!("exact({})", eo.exact_rule.as_ref().unwrap());
     }
     if eo.rule.is_some() {
-        return eo.rule.as_ref().unwrap().to_string();
+        return format!("{}{}", eo.rule.as_ref().unwrap(), list_args(eo));
     }
     "".to_string()
 }
 
+/// note that a rule names a link, as with resolve_links rules match where it leads
+fn list_link(eo: &EnvOptions) -> String {
+    if eo.acl_type != Acltype::Run || eo.resolve_links != Some(true) {
+        return "".to_string();
+    }
+    let path = match (&eo.exact_rule, &eo.rule) {
+        (Some(exact), _) => exact.split_whitespace().next(),
+        (None, Some(rule)) => rule
+            .trim_start_matches('^')
+            .split(|c: char| c.is_whitespace() || c == '$')
+            .next(),
+        (None, None) => None,
+    };
+    match path {
+        Some(path) if path.starts_with('/') => match resolve_binary(path) {
+            Some(resolved) if resolved != path => {
+                format!(" ({} links to {}, which rules match)", path, resolved)
+            }
+            _ => "".to_string(),
+        },
+        _ => "".to_string(),
+    }
+}
+
+/// return argN, args_max and args_rest for a listing
+fn list_args(eo: &EnvOptions) -> String {
+    if !eo.has_args() {
+        return "".to_string();
+    }
+    let mut args = vec![];
+    if let Some(arg_res) = &eo.args {
+        for (n, v) in arg_res {
+            args.push(format!("arg{}={}", n, v));
+        }
+    }
+    if let Some(args_max) = eo.args_max {
+        args.push(format!("args_max={}", args_max));
+    }
+    if let Some(args_rest) = &eo.args_rest {
+        args.push(format!("args_rest={}", args_rest));
+    }
+    format!(" args({})", args.join(" "))
+}
+
+/// return rlimit.X, nice and ioprio for a listing
+fn list_limits(eo: &EnvOptions) -> Option<String> {
+    let mut limits = vec![];
+    if let Some(rlimit) = &eo.rlimit {
+        for (resource, limit) in rlimit {
+            if *limit == RLIM_UNLIMITED {
+                limits.push(format!("rlimit.{}=unlimited", resource));
+            } else {
+                limits.push(format!("rlimit.{}={}", resource, limit));
+            }
+        }
+    }
+    if let Some(nice) = eo.nice {
+        limits.push(format!("nice={}", nice));
+    }
+    if let Some(ioprio) = &eo.ioprio {
+        limits.push(format!("ioprio={}", ioprio));
+    }
+    if limits.is_empty() {
+        return None;
+    }
+    Some(limits.join(" "))
+}
+
+/// return chroot, private_tmp, readonly_paths and hide_paths for a listing
+fn list_isolation(eo: &EnvOptions) -> Option<String> {
+    if !eo.isolated() {
+        return None;
+    }
+    let mut isolation = vec![];
+    if let Some(chroot) = &eo.chroot {
+        isolation.push(format!("chroot={}", chroot));
+    }
+    if eo.private_tmp == Some(true) {
+        isolation.push("private_tmp".to_string());
+    }
+    if let Some(paths) = &eo.readonly_paths {
+        isolation.push(format!("readonly_paths={}", paths.join(",")));
+    }
+    if let Some(paths) = &eo.hide_paths {
+        isolation.push(format!("hide_paths={}", paths.join(",")));
+    }
+    Some(isolation.join(" "))
+}
+
 /// return target or exact_target
 pub fn list_target(eo: &EnvOptions) -> String {
     if eo.exact_target.is_some() {
@@ -1606,6 +4366,14 @@ pub fn list_target(eo: &EnvOptions) -> String {
     "".to_string()
 }
 
+/// return the capabilities a target is limited to for a listing
+fn list_capabilities(eo: &EnvOptions) -> String {
+    match &eo.capabilities {
+        Some(capabilities) => format!(" caps({})", capabilities.join(",")),
+        None => "".to_string(),
+    }
+}
+
 /// return dir or exact_dir
 pub fn list_dir(eo: &EnvOptions) -> String {
     if eo.exact_dir.is_some() {
@@ -1650,87 +4418,633 @@ pub fn produce_list(vec_eo: &[EnvOptions], ro: &RunOptions) -> Vec<String> {
             prefixes.push(format!("upcomming({})", item.notbefore.unwrap()));
         }
 
-        if item.notafter.is_some() && item.notafter.unwrap() < ro.date {
-            prefixes.push(format!("expired({})", item.notafter.unwrap()));
+        if item.notafter.is_some() && item.notafter.unwrap() < ro.date {
+            prefixes.push(format!("expired({})", item.notafter.unwrap()));
+        }
+
+        if let Some(until) = excluded_until(item, &ro.date) {
+            prefixes.push(format!("frozen(until {})", until));
+        }
+
+        if let Some(schedule) = &item.schedule {
+            prefixes.push(format!("schedule({})", schedule));
+        }
+
+        if let Some(limits) = list_limits(item) {
+            prefixes.push(format!("limits({})", limits));
+        }
+
+        if let Some(isolation) = list_isolation(item) {
+            prefixes.push(format!("isolate({})", isolation));
+        }
+
+        match &item.reason {
+            Some(r) => {
+                if *r != ReasonType::Need(false) {
+                    prefixes.push(String::from("reason_required"));
+                }
+            }
+            None => {}
+        }
+
+        if item.acl_type != ro.acl_type {
+            continue;
+        }
+
+        if !item.permit() {
+            prefixes.push(String::from("not permitted"));
+        }
+
+        if !hostname_ok(item, &ro, None) {
+            continue;
+        }
+
+        if item.last.is_some() && item.last.unwrap() {
+            prefixes.push(String::from("last"));
+        }
+
+        let mut prefix = prefixes.join(", ");
+        if !prefix.is_empty() {
+            if item.acl_type != Acltype::List {
+                prefix = format!(" {} as ", prefix);
+            } else {
+                prefix = format!(" {} to ", prefix);
+            }
+        }
+        if last_file != item.file_name {
+            str_list.push(format!("  in file: {}", item.file_name));
+            last_file = &item.file_name;
+        }
+
+        if item.acl_type == Acltype::List {
+            str_list.push(format!(
+                "    {}:{}list: {}",
+                item.section,
+                prefix,
+                item.target.as_ref().unwrap()
+            ));
+            continue;
+        }
+
+        str_list.push(format!(
+            "    {}:{}{} (pass={},dirs={}): {}",
+            item.section,
+            prefix,
+            list_target(item) + &list_capabilities(item),
+            item.require_pass(),
+            list_dir(item),
+            list_rule(item) + &list_link(item)
+        ));
+    }
+    str_list
+}
+
+/// return result from search cache lookup
+pub fn search_path_cache(ro: &RunOptions, binary: &str) -> Option<String> {
+    match ro.located_bin.get(binary) {
+        Some(k) => {
+            if k.is_none() {
+                // println!("{} returning None (cached lookup)", item.section);
+                return None;
+            }
+            // println!("{} returning Some({})", item.section, k.as_ref().unwrap().to_string());
+            return Some(k.as_ref().unwrap().to_string());
+        }
+        None => {
+            // println!("{} returning None (not a cached lookup)", item.section);
+            None
+        }
+    }
+}
+
+/// split a digest= value into sha256:hex digests, in lower case
+pub fn parse_digests(value: &str) -> Result<Vec<String>, String> {
+    let mut digests = vec![];
+    for digest in value.split(|c: char| c == ',' || c.is_whitespace()) {
+        if digest.is_empty() {
+            continue;
+        }
+        match digest.split_once(':') {
+            Some((algorithm, hex))
+                if algorithm.eq_ignore_ascii_case("sha256")
+                    && hex.len() == 64
+                    && hex.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                digests.push(format!("sha256:{}", hex.to_lowercase()));
+            }
+            _ => {
+                return Err(format!(
+                    "{} is not sha256: followed by 64 hex digits",
+                    digest
+                ))
+            }
+        }
+    }
+    if digests.is_empty() {
+        return Err("no digest given".to_string());
+    }
+    Ok(digests)
+}
+
+/// the sha256:hex digest of a file's contents
+pub fn file_digest(path: &str) -> Result<String, std::io::Error> {
+    reader_digest(&mut File::open(path)?)
+}
+
+/// the sha256:hex digest of everything that can be read
+fn reader_digest(reader: &mut impl Read) -> Result<String, std::io::Error> {
+    let mut hasher = Sha256::new();
+    std::io::copy(reader, &mut hasher)?;
+    let hex: Vec<String> = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok(format!("sha256:{}", hex.join("")))
+}
+
+/// run f as root, then return to the user if privileges had been dropped
+pub fn as_root<T>(ro: &RunOptions, f: impl FnOnce() -> T) -> T {
+    if nix::unistd::geteuid().is_root() {
+        return f();
+    }
+    esc_privs();
+    let result = f();
+    drop_privs(ro);
+    result
+}
+
+/// the digest of a located binary, each binary is only read once. it is read as root, as a user
+/// may be permitted to run what they cannot read
+pub fn located_digest(ro: &mut RunOptions, path: &str) -> Option<String> {
+    if let Some(digest) = ro.located_digest.get(path) {
+        return digest.clone();
+    }
+    let digest = as_root(ro, || file_digest(path)).ok();
+    ro.located_digest.insert(path.to_string(), digest.clone());
+    digest
+}
+
+/// a reason to refuse the binary that search_path found, unless the section allows it
+pub fn unsafe_binary(entry: &EnvOptions, binary: &OpenBinary) -> Option<String> {
+    if entry.allow_unsafe_binary == Some(true) {
+        return None;
+    }
+    binary.fault()
+}
+
+/// where a binary leads through any links in its path, which resolve_links rules match
+pub fn resolve_binary(path: &str) -> Option<String> {
+    fs::canonicalize(path)
+        .ok()
+        .map(|canonical| canonical.to_string_lossy().to_string())
+}
+
+/// a binary opened once without following links, so the file that is checked is the one that runs
+pub struct OpenBinary {
+    pub path: String,
+    file: File,
+}
+
+#[cfg(target_os = "linux")]
+const OPEN_BINARY_FLAGS: i32 = libc::O_PATH | libc::O_NOFOLLOW;
+#[cfg(not(target_os = "linux"))]
+const OPEN_BINARY_FLAGS: i32 = libc::O_NOFOLLOW;
+
+impl OpenBinary {
+    pub fn open(path: &str) -> Result<OpenBinary, std::io::Error> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(OPEN_BINARY_FLAGS)
+            .open(path)?;
+        // O_PATH with O_NOFOLLOW opens a link itself rather than failing
+        if file.metadata()?.file_type().is_symlink() {
+            return Err(std::io::Error::from_raw_os_error(libc::ELOOP));
+        }
+        Ok(OpenBinary {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    /// open the file that path leads to, keeping path as its name, so that fault checks the
+    /// directories of both
+    pub fn open_resolved(path: &str) -> Result<OpenBinary, std::io::Error> {
+        let resolved = fs::canonicalize(path)?;
+        let mut binary = OpenBinary::open(&resolved.to_string_lossy())?;
+        binary.path = path.to_string();
+        Ok(binary)
+    }
+
+    /// the open file must be a root owned file that others cannot write, in directories that
+    /// others cannot write
+    pub fn fault(&self) -> Option<String> {
+        let stat_data = match nix::sys::stat::fstat(self.file.as_raw_fd()) {
+            Ok(stat_data) => stat_data,
+            Err(why) => return Some(format!("Could not stat {}: {}", self.path, why)),
+        };
+        if stat_data.st_mode & libc::S_IFMT != libc::S_IFREG {
+            return Some(format!("Refusing to run {} as it is not a file", self.path));
+        }
+        if stat_data.st_uid != 0 {
+            return Some(format!(
+                "Refusing to run {} as it is owned by uid {}",
+                self.path, stat_data.st_uid
+            ));
+        }
+        if stat_data.st_mode & 0o022 != 0 {
+            return Some(format!(
+                "Refusing to run {} as it is writable by group or other",
+                self.path
+            ));
+        }
+        binary_fault(&self.path)
+    }
+
+    /// the sha256:hex digest of the open file
+    pub fn digest(&self) -> Result<String, std::io::Error> {
+        // an O_PATH descriptor cannot be read, but can be opened again through /proc
+        #[cfg(target_os = "linux")]
+        return file_digest(&format!("/proc/self/fd/{}", self.file.as_raw_fd()));
+        #[cfg(not(target_os = "linux"))]
+        return reader_digest(&mut &self.file);
+    }
+
+    /// install a seccomp filter so that once this file is run, it and anything it starts cannot
+    /// run other programs. the filter cannot be removed and is kept across exec and fork.
+    /// a filter cannot tell one exec from the next, so each is passed to a watcher process that
+    /// allows them only until this process has run the file
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub fn noexec(&self) -> Result<(), std::io::Error> {
+        #[cfg(target_arch = "x86_64")]
+        const AUDIT_ARCH: u32 = 0xc000_003e;
+        #[cfg(target_arch = "aarch64")]
+        const AUDIT_ARCH: u32 = 0xc000_00b7;
+        // x32 system calls share the x86_64 arch with this bit set
+        const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+        let deny = libc::SECCOMP_RET_ERRNO | (libc::EACCES as u32 & libc::SECCOMP_RET_DATA);
+        let load = |offset: u32| libc::sock_filter {
+            code: (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16,
+            jt: 0,
+            jf: 0,
+            k: offset,
+        };
+        let jump = |k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let jump_ge = |k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: (libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let ret = |k: u32| libc::sock_filter {
+            code: (libc::BPF_RET | libc::BPF_K) as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+
+        // seccomp_data is nr, arch, instruction_pointer and then args
+        let mut filter = vec![
+            load(4),
+            jump(AUDIT_ARCH, 1, 0),
+            ret(deny),
+            load(0),
+            jump_ge(X32_SYSCALL_BIT, 0, 1),
+            ret(deny),
+            jump(libc::SYS_execve as u32, 1, 0),
+            jump(libc::SYS_execveat as u32, 0, 1),
+            ret(libc::SECCOMP_RET_USER_NOTIF),
+            ret(libc::SECCOMP_RET_ALLOW),
+        ];
+        let prog = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+
+        // the write end closes when this process runs the file, which is when execs stop
+        let mut ran = [0; 2];
+        if unsafe { libc::pipe2(ran.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let close_all = |fds: &[i32]| {
+            for fd in fds {
+                unsafe { libc::close(*fd) };
+            }
+        };
+        let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, libc::getpid(), 0) } as i32;
+        if pidfd < 0 {
+            let why = std::io::Error::last_os_error();
+            close_all(&ran);
+            return Err(why);
+        }
+        let listener = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &prog,
+            )
+        } as i32;
+        if listener < 0 {
+            let why = std::io::Error::last_os_error();
+            close_all(&[ran[0], ran[1], pidfd]);
+            return Err(why);
+        }
+
+        // the watcher is forked twice, so it is not a child that the command could wait for
+        match unsafe { libc::fork() } {
+            -1 => {
+                let why = std::io::Error::last_os_error();
+                close_all(&[ran[0], ran[1], pidfd, listener]);
+                // execs are now refused, as nothing answers the filter
+                return Err(why);
+            }
+            0 => unsafe {
+                if libc::fork() == 0 {
+                    watch_exec(listener, ran[0], pidfd);
+                }
+                libc::_exit(0);
+            },
+            child => {
+                let mut status = 0;
+                unsafe { libc::waitpid(child, &mut status, 0) };
+            }
+        }
+        close_all(&[ran[0], pidfd, listener]);
+        Ok(())
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    pub fn noexec(&self) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "noexec is not supported on this platform",
+        ))
+    }
+
+    /// run the open file with args and the current environment, only returns on error
+    pub fn exec(&self, args: &[String]) -> std::io::Error {
+        let to_cstrings = |strings: Vec<Vec<u8>>| -> Result<Vec<CString>, std::io::Error> {
+            strings
+                .into_iter()
+                .map(|s| CString::new(s).map_err(std::io::Error::from))
+                .collect()
+        };
+        let argv = match to_cstrings(args.iter().map(|a| a.as_bytes().to_vec()).collect()) {
+            Ok(argv) => argv,
+            Err(why) => return why,
+        };
+        let envp = match to_cstrings(
+            env::vars_os()
+                .map(|(k, v)| {
+                    let mut pair = k.into_vec();
+                    pair.push(b'=');
+                    pair.extend(v.into_vec());
+                    pair
+                })
+                .collect(),
+        ) {
+            Ok(envp) => envp,
+            Err(why) => return why,
+        };
+        let mut argv_ptrs: Vec<*const libc::c_char> = argv.iter().map(|a| a.as_ptr()).collect();
+        argv_ptrs.push(std::ptr::null());
+        let mut envp_ptrs: Vec<*const libc::c_char> = envp.iter().map(|e| e.as_ptr()).collect();
+        envp_ptrs.push(std::ptr::null());
+
+        self.exec_ptrs(&argv_ptrs, &envp_ptrs)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn exec_ptrs(
+        &self,
+        argv: &[*const libc::c_char],
+        envp: &[*const libc::c_char],
+    ) -> std::io::Error {
+        let fd = self.file.as_raw_fd();
+        self.fexecve(argv, envp);
+        let why = std::io::Error::last_os_error();
+        if why.raw_os_error() != Some(libc::ENOENT) {
+            return why;
         }
 
-        match &item.reason {
-            Some(r) => {
-                if *r != ReasonType::Need(false) {
-                    prefixes.push(String::from("reason_required"));
-                }
-            }
-            None => {}
-        }
+        // a script's interpreter reads it from /dev/fd, which close on exec would remove
+        unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        self.fexecve(argv, envp);
+        std::io::Error::last_os_error()
+    }
 
-        if item.acl_type != ro.acl_type {
-            continue;
-        }
+    /// execveat as the noexec filter expects it, rather than however libc implements fexecve
+    #[cfg(target_os = "linux")]
+    fn fexecve(&self, argv: &[*const libc::c_char], envp: &[*const libc::c_char]) {
+        let empty = b"\0";
+        unsafe {
+            libc::syscall(
+                libc::SYS_execveat,
+                self.file.as_raw_fd(),
+                empty.as_ptr(),
+                argv.as_ptr(),
+                envp.as_ptr(),
+                libc::AT_EMPTY_PATH,
+            )
+        };
+    }
 
-        if !item.permit() {
-            prefixes.push(String::from("not permitted"));
-        }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn fexecve(&self, argv: &[*const libc::c_char], envp: &[*const libc::c_char]) {
+        unsafe { libc::fexecve(self.file.as_raw_fd(), argv.as_ptr(), envp.as_ptr()) };
+    }
 
-        if !hostname_ok(item, &ro, None) {
-            continue;
-        }
+    /// macos has no fexecve, so the path is run
+    #[cfg(target_os = "macos")]
+    fn exec_ptrs(
+        &self,
+        argv: &[*const libc::c_char],
+        envp: &[*const libc::c_char],
+    ) -> std::io::Error {
+        let path = match CString::new(self.path.as_bytes()) {
+            Ok(path) => path,
+            Err(why) => return why.into(),
+        };
+        unsafe { libc::execve(path.as_ptr(), argv.as_ptr(), envp.as_ptr()) };
+        std::io::Error::last_os_error()
+    }
+}
 
-        if item.last.is_some() && item.last.unwrap() {
-            prefixes.push(String::from("last"));
+impl AsRawFd for OpenBinary {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// answer the noexec filter, allowing execs until the ran pipe closes and refusing them after,
+/// until the process that installed the filter exits
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn watch_exec(listener: i32, ran: i32, pidfd: i32) -> ! {
+    unsafe { libc::setsid() };
+    // nothing else is needed, and the binary, terminal or pipes should not be held open
+    if let Ok(dir) = fs::read_dir("/proc/self/fd") {
+        let fds: Vec<i32> = dir
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect();
+        for fd in fds {
+            if fd != listener && fd != ran && fd != pidfd {
+                unsafe { libc::close(fd) };
+            }
         }
+    }
 
-        let mut prefix = prefixes.join(", ");
-        if !prefix.is_empty() {
-            if item.acl_type != Acltype::List {
-                prefix = format!(" {} as ", prefix);
-            } else {
-                prefix = format!(" {} to ", prefix);
+    let has_run = || {
+        let mut poll = libc::pollfd {
+            fd: ran,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut poll, 1, 0) > 0 }
+    };
+
+    loop {
+        let mut polls = [
+            libc::pollfd {
+                fd: listener,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: pidfd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        if unsafe { libc::poll(polls.as_mut_ptr(), 2, -1) } < 0 {
+            if std::io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                continue;
             }
+            break;
         }
-        if last_file != item.file_name {
-            str_list.push(format!("  in file: {}", item.file_name));
-            last_file = &item.file_name;
+        if polls[1].revents != 0 || polls[0].revents & (libc::POLLHUP | libc::POLLERR) != 0 {
+            break;
+        }
+        if polls[0].revents & libc::POLLIN == 0 {
+            continue;
         }
 
-        if item.acl_type == Acltype::List {
-            str_list.push(format!(
-                "    {}:{}list: {}",
-                item.section,
-                prefix,
-                item.target.as_ref().unwrap()
-            ));
+        let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(listener, libc::SECCOMP_IOCTL_NOTIF_RECV, &mut notif) } != 0 {
             continue;
         }
+        // any exec made after the file has run was made by the command
+        let mut resp: libc::seccomp_notif_resp = unsafe { std::mem::zeroed() };
+        resp.id = notif.id;
+        if has_run() {
+            resp.error = -libc::EACCES;
+        } else {
+            resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32;
+        }
+        unsafe { libc::ioctl(listener, libc::SECCOMP_IOCTL_NOTIF_SEND, &resp) };
+    }
+    unsafe { libc::_exit(0) };
+}
 
-        str_list.push(format!(
-            "    {}:{}{} (pass={},dirs={}): {}",
-            item.section,
-            prefix,
-            list_target(item),
-            item.require_pass(),
-            list_dir(item),
-            list_rule(item)
-        ));
+/// true if the section has no digest or the binary that search_path found has one of them
+pub fn digest_ok(item: &EnvOptions, ro: &mut RunOptions) -> bool {
+    let digests = match &item.digest {
+        Some(digests) => digests,
+        None => return true,
+    };
+    let binary = match &ro.cloned_args {
+        Some(cloned_args) => cloned_args[0].clone(),
+        None => return false,
+    };
+    match located_digest(ro, &binary) {
+        Some(digest) => digests.contains(&digest),
+        None => false,
     }
-    str_list
 }
 
-/// return result from search cache lookup
-pub fn search_path_cache(ro: &RunOptions, binary: &str) -> Option<String> {
-    match ro.located_bin.get(binary) {
-        Some(k) => {
-            if k.is_none() {
-                // println!("{} returning None (cached lookup)", item.section);
-                return None;
-            }
-            // println!("{} returning Some({})", item.section, k.as_ref().unwrap().to_string());
-            return Some(k.as_ref().unwrap().to_string());
-        }
-        None => {
-            // println!("{} returning None (not a cached lookup)", item.section);
-            None
+/// the program a run section names, from exact_rule, arg0 or the leading path of the rule
+pub fn section_program(item: &EnvOptions) -> Option<String> {
+    let literal = |c: char| c.is_alphanumeric() || "/._-+".contains(c);
+
+    if let Some(exact) = &item.exact_rule {
+        return exact
+            .split(' ')
+            .next()
+            .filter(|p| p.starts_with('/'))
+            .map(|p| p.to_string());
+    }
+
+    let (pattern, arg0) = match item.args.as_ref().and_then(|args| args.get(&0)) {
+        Some(arg0) => (arg0.as_str(), true),
+        None => (item.rule.as_deref()?, false),
+    };
+    let pattern = pattern.trim_start_matches('^');
+    let path: String = pattern.chars().take_while(|c| literal(*c)).collect();
+    let rest = &pattern[path.len()..];
+    let whole = rest.is_empty()
+        || rest.starts_with('$')
+        || (!arg0 && (rest.starts_with(' ') || rest.starts_with("\\s")));
+    if !path.starts_with('/') || !whole {
+        return None;
+    }
+    Some(path)
+}
+
+/// lines for please -c --digest, the current digest of each run section's program
+pub fn digest_report(vec_eo: &[EnvOptions]) -> Vec<String> {
+    let mut report = vec![];
+
+    for item in vec_eo {
+        if item.acl_type != Acltype::Run {
+            continue;
         }
+        let location = format!(
+            "[{}] at {}",
+            item.section,
+            section_location(&item.file_name, item.line)
+        );
+        let program = match section_program(item) {
+            Some(program) => program,
+            None => {
+                if item.digest.is_some() {
+                    report.push(format!("{}: cannot find a program in the rule", location));
+                }
+                continue;
+            }
+        };
+        let digest = match file_digest(&program) {
+            Ok(digest) => digest,
+            Err(x) => {
+                report.push(format!("{}: {}: {}", location, program, x));
+                continue;
+            }
+        };
+        let state = match &item.digest {
+            None => "not pinned",
+            Some(digests) if digests.contains(&digest) => "matches",
+            Some(_) => "differs",
+        };
+        report.push(format!(
+            "{}: {} digest={} ({})",
+            location, program, digest, state
+        ));
     }
+    report
 }
 
 /// if binary is not an absolute/relative path, look for it in usual places
@@ -1890,6 +5204,518 @@ pub fn set_environment(
     }
 }
 
+/// an rlimit.X value that lifts the limit
+pub const RLIM_UNLIMITED: u64 = u64::MAX;
+
+/// read an rlimit.X value, a number, with K, M, G or T for rlimit.as, or unlimited
+pub fn parse_rlimit(resource: &str, value: &str) -> Result<u64, String> {
+    if !["nofile", "as", "cpu", "nproc"].contains(&resource) {
+        return Err(format!(
+            "unknown resource {}, expected nofile, as, cpu or nproc",
+            resource
+        ));
+    }
+    if value == "unlimited" {
+        return Ok(RLIM_UNLIMITED);
+    }
+    let (count, shift) = match value.chars().last() {
+        Some(unit) if resource == "as" && "KMGT".contains(unit.to_ascii_uppercase()) => (
+            &value[..value.len() - 1],
+            10 * (1 + "KMGT".find(unit.to_ascii_uppercase()).unwrap() as u32),
+        ),
+        _ => (value, 0),
+    };
+    let count = count.parse::<u64>().map_err(|x| x.to_string())?;
+    count
+        .checked_mul(1 << shift)
+        .filter(|limit| *limit != RLIM_UNLIMITED)
+        .ok_or_else(|| "too large".to_string())
+}
+
+/// read an ioprio value, a class of realtime, best-effort or idle, then an optional :level of 0
+/// to 7, returning the value for ioprio_set
+pub fn parse_ioprio(value: &str) -> Result<i32, String> {
+    let (class, level) = match value.split_once(':') {
+        Some((class, level)) => match level.parse::<i32>() {
+            Ok(level) if (0..=7).contains(&level) => (class, level),
+            _ => return Err(format!("level {} is not 0 to 7", level)),
+        },
+        None => (value, 4),
+    };
+    let class = match class {
+        "realtime" => 1,
+        "best-effort" => 2,
+        "idle" if value == "idle" => 3,
+        "idle" => return Err("idle does not take a level".to_string()),
+        _ => {
+            return Err(format!(
+                "unknown class {}, expected realtime, best-effort or idle",
+                class
+            ))
+        }
+    };
+    let level = if class == 3 { 0 } else { level };
+    Ok(class << 13 | level)
+}
+
+/// set the resource limits and priorities of a section on this process, which the program inherits
+pub fn set_limits(entry: &EnvOptions) -> Result<(), String> {
+    if let Some(rlimit) = &entry.rlimit {
+        for (resource, limit) in rlimit {
+            let which = match resource.as_str() {
+                "nofile" => libc::RLIMIT_NOFILE,
+                "as" => libc::RLIMIT_AS,
+                "cpu" => libc::RLIMIT_CPU,
+                "nproc" => libc::RLIMIT_NPROC,
+                _ => return Err(format!("unknown resource {}", resource)),
+            };
+            let limit = if *limit == RLIM_UNLIMITED {
+                libc::RLIM_INFINITY
+            } else {
+                *limit as libc::rlim_t
+            };
+            let rlim = libc::rlimit {
+                rlim_cur: limit,
+                rlim_max: limit,
+            };
+            if unsafe { libc::setrlimit(which, &rlim) } != 0 {
+                return Err(format!(
+                    "rlimit.{}: {}",
+                    resource,
+                    std::io::Error::last_os_error()
+                ));
+            }
+        }
+    }
+
+    if let Some(nice) = entry.nice {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            return Err(format!("nice: {}", std::io::Error::last_os_error()));
+        }
+    }
+
+    if let Some(ioprio) = &entry.ioprio {
+        set_ioprio(parse_ioprio(ioprio)?).map_err(|x| format!("ioprio: {}", x))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_ioprio(ioprio: i32) -> Result<(), std::io::Error> {
+    // IOPRIO_WHO_PROCESS, this process
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, 1, 0, ioprio) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_ioprio(_ioprio: i32) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "ioprio is not supported on this platform",
+    ))
+}
+
+/// split a readonly_paths= or hide_paths= value into absolute paths
+pub fn parse_paths(value: &str) -> Result<Vec<String>, String> {
+    let mut paths = vec![];
+    for path in value.split(|c: char| c == ',' || c.is_whitespace()) {
+        if path.is_empty() {
+            continue;
+        }
+        if !path.starts_with('/') {
+            return Err(format!("{} should start with /", path));
+        }
+        paths.push(path.to_string());
+    }
+    if paths.is_empty() {
+        return Err("no path given".to_string());
+    }
+    Ok(paths)
+}
+
+/// give this process, and the program it becomes, its own view of the filesystem. paths are
+/// within the chroot, and the directory is changed to directory, or / in a chroot
+#[cfg(target_os = "linux")]
+pub fn isolate(entry: &EnvOptions, directory: Option<&str>) -> Result<(), String> {
+    if !entry.isolated() {
+        return Ok(());
+    }
+
+    let root = entry.chroot.as_deref().unwrap_or("").trim_end_matches('/');
+    if let Some(chroot) = &entry.chroot {
+        if let Some(fault) = path_fault(chroot, Path::new(chroot), &[0], "chroot to") {
+            return Err(fault);
+        }
+    }
+
+    if unsafe { libc::unshare(libc::CLONE_NEWNS) } != 0 {
+        return Err(format!("unshare: {}", std::io::Error::last_os_error()));
+    }
+    // mounts below stay in this namespace
+    isolate_mount(None, "/", None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+
+    if entry.private_tmp == Some(true) {
+        for tmp in ["/tmp", "/var/tmp"] {
+            let target = format!("{}{}", root, tmp);
+            if Path::new(&target).is_dir() {
+                isolate_mount(
+                    Some("tmpfs"),
+                    &target,
+                    Some("tmpfs"),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    Some("mode=1777"),
+                )?;
+            }
+        }
+    }
+
+    for path in entry.readonly_paths.iter().flatten() {
+        let target = format!("{}{}", root, path);
+        // mountinfo has the path with its links followed
+        let target = fs::canonicalize(&target)
+            .map_err(|x| format!("{}: {}", target, x))?
+            .to_string_lossy()
+            .to_string();
+        isolate_mount(
+            Some(&target),
+            &target,
+            None,
+            libc::MS_BIND | libc::MS_REC,
+            None,
+        )?;
+        // a remount changes only the mount at that point, not those below it
+        for (point, flags) in mounts_below(&target)? {
+            isolate_mount(
+                None,
+                &point,
+                None,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                None,
+            )?;
+        }
+    }
+
+    for path in entry.hide_paths.iter().flatten() {
+        let target = format!("{}{}", root, path);
+        let meta = fs::metadata(&target).map_err(|x| format!("{}: {}", target, x))?;
+        if meta.is_dir() {
+            isolate_mount(
+                Some("tmpfs"),
+                &target,
+                Some("tmpfs"),
+                libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                Some("mode=000"),
+            )?;
+        } else {
+            isolate_mount(Some("/dev/null"), &target, None, libc::MS_BIND, None)?;
+            isolate_mount(
+                None,
+                &target,
+                None,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY,
+                None,
+            )?;
+        }
+    }
+
+    if let Some(chroot) = &entry.chroot {
+        nix::unistd::chroot(chroot.as_str()).map_err(|x| format!("chroot {}: {}", chroot, x))?;
+    }
+
+    // the directory may now be a different one, or not there at all
+    let directory = match directory {
+        Some(directory) => directory,
+        None if entry.chroot.is_some() => "/",
+        None => return Ok(()),
+    };
+    env::set_current_dir(directory).map_err(|x| format!("cannot cd into {}: {}", directory, x))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn isolate(entry: &EnvOptions, _directory: Option<&str>) -> Result<(), String> {
+    if !entry.isolated() {
+        return Ok(());
+    }
+    Err(
+        "chroot, private_tmp, readonly_paths and hide_paths are only supported on Linux"
+            .to_string(),
+    )
+}
+
+/// the mount points at or below path, from /proc/self/mountinfo, each with the nosuid, nodev,
+/// noexec and atime flags that a remount would otherwise clear
+#[cfg(target_os = "linux")]
+fn mounts_below(path: &str) -> Result<Vec<(String, libc::c_ulong)>, String> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|x| format!("/proc/self/mountinfo: {}", x))?;
+    let below = format!("{}/", path.trim_end_matches('/'));
+
+    let mut mounts = vec![];
+    for line in mountinfo.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let point = mountinfo_unescape(fields[4]);
+        if point != path && !point.starts_with(&below) {
+            continue;
+        }
+        let flags = fields[5].split(',').fold(0, |flags, option| {
+            flags
+                | match option {
+                    "nosuid" => libc::MS_NOSUID,
+                    "nodev" => libc::MS_NODEV,
+                    "noexec" => libc::MS_NOEXEC,
+                    "noatime" => libc::MS_NOATIME,
+                    "nodiratime" => libc::MS_NODIRATIME,
+                    "relatime" => libc::MS_RELATIME,
+                    "strictatime" => libc::MS_STRICTATIME,
+                    _ => 0,
+                }
+        });
+        mounts.push((point, flags));
+    }
+    Ok(mounts)
+}
+
+/// undo the octal escapes that mountinfo gives space, tab, newline and backslash
+#[cfg(target_os = "linux")]
+fn mountinfo_unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// mount(2) for isolate, naming the target in any error
+#[cfg(target_os = "linux")]
+fn isolate_mount(
+    source: Option<&str>,
+    target: &str,
+    fstype: Option<&str>,
+    flags: libc::c_ulong,
+    data: Option<&str>,
+) -> Result<(), String> {
+    let cstring = |s: &str| CString::new(s).map_err(|x| format!("{}: {}", target, x));
+    let source = source.map(cstring).transpose()?;
+    let c_target = cstring(target)?;
+    let fstype = fstype.map(cstring).transpose()?;
+    let data = data.map(cstring).transpose()?;
+    let ptr = |s: &Option<CString>| s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+
+    let rc = unsafe {
+        libc::mount(
+            ptr(&source),
+            c_target.as_ptr(),
+            ptr(&fstype),
+            flags,
+            ptr(&data) as *const libc::c_void,
+        )
+    };
+    if rc != 0 {
+        return Err(format!(
+            "mount {}: {}",
+            target,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// linux capabilities, each at the position of its number
+const CAPABILITIES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// split a capabilities= value into lower case cap_ names, the cap_ may be left out
+pub fn parse_capabilities(value: &str) -> Result<Vec<String>, String> {
+    let mut capabilities = vec![];
+    for capability in value.split(|c: char| c == ',' || c.is_whitespace()) {
+        if capability.is_empty() {
+            continue;
+        }
+        let mut capability = capability.to_lowercase();
+        if !capability.starts_with("cap_") {
+            capability = format!("cap_{}", capability);
+        }
+        if !CAPABILITIES.contains(&capability.as_str()) {
+            return Err(format!("unknown capability {}", capability));
+        }
+        if !capabilities.contains(&capability) {
+            capabilities.push(capability);
+        }
+    }
+    if capabilities.is_empty() {
+        return Err("no capability given".to_string());
+    }
+    Ok(capabilities)
+}
+
+/// the numbers of capabilities
+fn capability_numbers(capabilities: &[String]) -> Vec<usize> {
+    capabilities
+        .iter()
+        .filter_map(|c| CAPABILITIES.iter().position(|known| known == c))
+        .collect()
+}
+
+/// while still root, remove every other capability from the bounding set, so nothing run can
+/// gain them, and keep the rest through set_privs
+#[cfg(target_os = "linux")]
+pub fn keep_capabilities(capabilities: &[String]) -> Result<(), String> {
+    let wanted = capability_numbers(capabilities);
+    for cap in 0..64 {
+        if unsafe { libc::prctl(libc::PR_CAPBSET_READ, cap, 0, 0, 0) } < 0 {
+            // past the last capability this kernel has
+            break;
+        }
+        if !wanted.contains(&(cap as usize))
+            && unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) } != 0
+        {
+            return Err(format!(
+                "dropping capability {}: {}",
+                cap,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    if unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) } != 0 {
+        return Err(format!(
+            "keeping capabilities: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// after set_privs, limit the capabilities kept to those given and make them ambient, so the
+/// program run has them too
+#[cfg(target_os = "linux")]
+pub fn raise_capabilities(capabilities: &[String]) -> Result<(), String> {
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: libc::c_int,
+    }
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    let wanted = capability_numbers(capabilities);
+    let mut data = [CapData::default(); 2];
+    for cap in &wanted {
+        let bit = 1 << (cap % 32);
+        data[cap / 32].effective |= bit;
+        data[cap / 32].permitted |= bit;
+        data[cap / 32].inheritable |= bit;
+    }
+    // _LINUX_CAPABILITY_VERSION_3, this process
+    let header = CapHeader {
+        version: 0x20080522,
+        pid: 0,
+    };
+    if unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } != 0 {
+        return Err(format!(
+            "setting capabilities: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    for cap in wanted {
+        if unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE,
+                cap as libc::c_ulong,
+                0,
+                0,
+            )
+        } != 0
+        {
+            return Err(format!(
+                "raising {}: {}",
+                CAPABILITIES[cap],
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn keep_capabilities(_capabilities: &[String]) -> Result<(), String> {
+    Err("capabilities are only supported on Linux".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn raise_capabilities(_capabilities: &[String]) -> Result<(), String> {
+    Err("capabilities are only supported on Linux".to_string())
+}
+
 pub fn bad_priv_msg() {
     println!("I cannot set privs. Exiting as not installed correctly.");
 }
@@ -2011,6 +5837,31 @@ pub fn log_action(service: &str, result: &str, ro: &RunOptions, command: &str) -
         None => "".to_string(),
     };
 
+    // the binary that was hashed to match a digest
+    let binary = match &ro.cloned_args {
+        Some(cloned_args) if ro.acl_type == Acltype::Run => {
+            match ro.located_digest.get(&cloned_args[0]) {
+                Some(Some(digest)) => format!(
+                    " binary=\"{}\" digest=\"{}\"",
+                    escape_log(&cloned_args[0]),
+                    digest
+                ),
+                _ => "".to_string(),
+            }
+        }
+        _ => "".to_string(),
+    };
+
+    let session = match &ro.session {
+        Some(session) => format!(" session=\"{}\"", session),
+        None => "".to_string(),
+    };
+
+    let outcome = match &ro.outcome {
+        Some(outcome) => format!(" {}", outcome),
+        None => "".to_string(),
+    };
+
     match syslog::unix(formatter) {
         Err(_e) => println!("Could not connect to syslog"),
         Ok(mut writer) => {
@@ -2018,7 +5869,7 @@ pub fn log_action(service: &str, result: &str, ro: &RunOptions, command: &str) -
 
             writer
                 .err(format!(
-                    "user=\"{}\" cwd=\"{}\" tty=\"{}\" action=\"{}\" target=\"{}\" type=\"{}\" reason=\"{}\" command=\"{}\" matching_section=\"{}\"",
+                    "user=\"{}\" cwd=\"{}\" tty=\"{}\" action=\"{}\" target=\"{}\" type=\"{}\" reason=\"{}\" command=\"{}\" matching_section=\"{}\"{}{}{}",
                     escape_log( &ro.name ),
                     escape_log( &cwd ),
                     if tty_name.is_none() {
@@ -2036,6 +5887,9 @@ pub fn log_action(service: &str, result: &str, ro: &RunOptions, command: &str) -
                     },
                     escape_log( command ),
                     matching_env,
+                    binary,
+                    session,
+                    outcome,
                 ))
                 .expect("could not write error message");
         }
@@ -0,0 +1,678 @@
+//    please
+//    Copyright (C) 2020-2021 ed neville
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! run a command on a pseudo-terminal of its own, so that it cannot reach the user's terminal,
+//! and pass on what goes between them, recording it if asked
+
+use crate::record::Recording;
+use crate::{drop_privs, esc_privs, log_action, set_privs, EnvOptions, RunOptions};
+use std::ffi::CStr;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+/// signals that are passed on to the command
+const FORWARD_SIGNALS: [libc::c_int; 6] = [
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+
+/// the write end of the pipe that signal_handler wakes the relay with
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn signal_handler(signal: libc::c_int) {
+    let byte = signal as u8;
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::Relaxed),
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        )
+    };
+}
+
+fn os_error(action: &str) -> String {
+    format!("{}: {}", action, std::io::Error::last_os_error())
+}
+
+/// the user's terminal and what the relay does with it
+struct Terminal {
+    /// a descriptor of the terminal, for its mode and size
+    fd: RawFd,
+    /// true if input is read from the terminal and passed to the command
+    input: bool,
+    /// where the command's output is written
+    output: RawFd,
+    /// the mode to put back when please exits or stops
+    termios: libc::termios,
+}
+
+impl Terminal {
+    /// the first of stdin, stdout or stderr that is a terminal, or None if there is no terminal
+    /// for the command to reach
+    fn find() -> Option<Terminal> {
+        let fd = (0..3).find(|fd| unsafe { libc::isatty(*fd) } == 1)?;
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+            return None;
+        }
+        let output = [1, 2, 0]
+            .iter()
+            .copied()
+            .find(|fd| unsafe { libc::isatty(*fd) } == 1)
+            .unwrap();
+        Some(Terminal {
+            fd,
+            input: fd == 0,
+            output,
+            termios,
+        })
+    }
+
+    /// pass keys through as they are typed, the pseudo-terminal does the rest
+    fn raw(&self) {
+        if self.input {
+            let mut raw = self.termios;
+            unsafe {
+                libc::cfmakeraw(&mut raw);
+                libc::tcsetattr(self.fd, libc::TCSAFLUSH, &raw);
+            }
+        }
+    }
+
+    fn restore(&self) {
+        if self.input {
+            unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.termios) };
+        }
+    }
+
+    /// give fd the size of the terminal
+    fn copy_size(&self, fd: RawFd) {
+        let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+        if unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut size) } == 0 {
+            unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) };
+        }
+    }
+}
+
+fn close_on_exec(fd: RawFd) {
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+}
+
+/// open a pseudo-terminal, returning the master descriptor and the path of the slave
+fn open_pty() -> Result<(RawFd, String), String> {
+    let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if master < 0 {
+        return Err(os_error("posix_openpt"));
+    }
+    close_on_exec(master);
+    if unsafe { libc::grantpt(master) } != 0 || unsafe { libc::unlockpt(master) } != 0 {
+        let error = os_error("unlockpt");
+        unsafe { libc::close(master) };
+        return Err(error);
+    }
+    let name = unsafe { libc::ptsname(master) };
+    if name.is_null() {
+        let error = os_error("ptsname");
+        unsafe { libc::close(master) };
+        return Err(error);
+    }
+    let slave = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .to_string();
+    Ok((master, slave))
+}
+
+/// a stream that please passes on between the user and the command
+struct Channel {
+    from: RawFd,
+    to: RawFd,
+    /// how it is recorded, i for input, o for output or e for errors
+    stream: char,
+    /// close to when from ends, so the command sees the end of its input
+    close: bool,
+    open: bool,
+}
+
+/// a pipe in place of fd for the command, returning the end the command has and the end please
+/// keeps
+fn pipe_for(fd: RawFd) -> Result<(RawFd, RawFd), String> {
+    let mut ends = [0; 2];
+    if unsafe { libc::pipe(ends.as_mut_ptr()) } != 0 {
+        return Err(os_error("pipe"));
+    }
+    close_on_exec(ends[0]);
+    close_on_exec(ends[1]);
+    if fd == 0 {
+        Ok((ends[0], ends[1]))
+    } else {
+        Ok((ends[1], ends[0]))
+    }
+}
+
+/// how the command ended, as seen by please waiting for it
+pub struct Outcome {
+    /// the wait status
+    pub status: libc::c_int,
+    pub duration: Duration,
+    pub rusage: libc::rusage,
+}
+
+impl Outcome {
+    /// the exit code or signal, wall time and resources used, for the log
+    pub fn describe(&self) -> String {
+        let ended = if libc::WIFSIGNALED(self.status) {
+            format!("signal=\"{}\"", libc::WTERMSIG(self.status))
+        } else {
+            format!("exit=\"{}\"", libc::WEXITSTATUS(self.status))
+        };
+        let seconds = |time: libc::timeval| format!("{}.{:06}", time.tv_sec, time.tv_usec);
+        format!(
+            "{} duration=\"{}.{:03}\" utime=\"{}\" stime=\"{}\" maxrss=\"{}\"",
+            ended,
+            self.duration.as_secs(),
+            self.duration.subsec_millis(),
+            seconds(self.rusage.ru_utime),
+            seconds(self.rusage.ru_stime),
+            self.rusage.ru_maxrss
+        )
+    }
+
+    /// exit with the status of the command, or be killed by the same signal
+    pub fn exit(&self) -> ! {
+        if libc::WIFSIGNALED(self.status) {
+            let signal = libc::WTERMSIG(self.status);
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::kill(libc::getpid(), signal);
+            }
+            std::process::exit(128 + signal);
+        }
+        std::process::exit(libc::WEXITSTATUS(self.status));
+    }
+}
+
+/// fork, with use_pty or when recording giving the child a new session on a pseudo-terminal in
+/// place of each of stdin, stdout and stderr that is a terminal, and when recording a pipe in
+/// place of each that is not. the child gets None and returns to run the command. please stays
+/// as the parent, passing input, output, window size changes and signals between the user and
+/// the command, and gets how it ended. with supervise the parent first gives up root for good,
+/// leaving signals to a forwarder. with nothing to do this returns None without forking
+pub fn fork_pty(
+    ro: &RunOptions,
+    entry: &EnvOptions,
+    mut recording: Option<Recording>,
+) -> Result<Option<Outcome>, String> {
+    let supervise = entry.supervise == Some(true);
+    let terminal = if entry.use_pty == Some(true) || recording.is_some() {
+        Terminal::find()
+    } else {
+        None
+    };
+    if terminal.is_none() && recording.is_none() && !supervise {
+        return Ok(None);
+    }
+
+    let mut channels = vec![];
+    // the descriptor for the command in place of each of stdin, stdout and stderr
+    let mut replace: Vec<(RawFd, RawFd)> = vec![];
+    let mut pty = None;
+
+    if let Some(terminal) = &terminal {
+        let (master, slave_path) = open_pty()?;
+        let slave_cpath = std::ffi::CString::new(slave_path.as_str()).unwrap();
+        let slave = unsafe { libc::open(slave_cpath.as_ptr(), libc::O_RDWR | libc::O_NOCTTY) };
+        if slave < 0 {
+            return Err(os_error(&slave_path));
+        }
+        close_on_exec(slave);
+        unsafe { libc::tcsetattr(slave, libc::TCSANOW, &terminal.termios) };
+        terminal.copy_size(master);
+
+        if terminal.input {
+            channels.push(Channel {
+                from: 0,
+                to: master,
+                stream: 'i',
+                close: false,
+                open: true,
+            });
+        }
+        channels.push(Channel {
+            from: master,
+            to: terminal.output,
+            stream: 'o',
+            close: false,
+            open: true,
+        });
+        pty = Some((master, slave));
+    }
+
+    for fd in 0..3 {
+        if unsafe { libc::isatty(fd) } == 1 {
+            if let Some((_, slave)) = pty {
+                replace.push((fd, slave));
+            }
+        } else if recording.is_some() {
+            let (theirs, ours) = pipe_for(fd)?;
+            replace.push((fd, theirs));
+            channels.push(Channel {
+                from: if fd == 0 { 0 } else { ours },
+                to: if fd == 0 { ours } else { fd },
+                stream: ['i', 'o', 'e'][fd as usize],
+                close: fd == 0,
+                open: true,
+            });
+        }
+    }
+
+    let mut signal_pipe = [0; 2];
+    if unsafe { libc::pipe(signal_pipe.as_mut_ptr()) } != 0 {
+        return Err(os_error("pipe"));
+    }
+    for fd in signal_pipe {
+        close_on_exec(fd);
+        unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) };
+    }
+
+    let started = Instant::now();
+    match unsafe { libc::fork() } {
+        -1 => Err(os_error("fork")),
+        0 => {
+            unsafe {
+                libc::close(signal_pipe[0]);
+                libc::close(signal_pipe[1]);
+                // without a pseudo-terminal the command stays in the process group of please,
+                // so it is sent what the user types at the terminal, such as ^C
+                if let Some((master, slave)) = pty {
+                    if libc::setsid() < 0 {
+                        return Err(os_error("setsid"));
+                    }
+                    libc::close(master);
+                    if libc::ioctl(slave, libc::TIOCSCTTY, 0) != 0 {
+                        return Err(os_error("TIOCSCTTY"));
+                    }
+                }
+                for (fd, theirs) in &replace {
+                    if libc::dup2(*theirs, *fd) < 0 {
+                        return Err(os_error("dup2"));
+                    }
+                }
+            }
+            // the rest are close on exec
+            Ok(None)
+        }
+        child => {
+            let forwarder = if supervise {
+                match fork_forwarder(ro, child) {
+                    Ok(forwarder) => Some(forwarder),
+                    Err(x) => {
+                        unsafe { libc::kill(child, libc::SIGKILL) };
+                        return Err(x);
+                    }
+                }
+            } else {
+                None
+            };
+            if supervise && !(esc_privs() && set_privs(&ro.name, ro.original_uid, ro.original_gid))
+            {
+                unsafe { libc::kill(child, libc::SIGKILL) };
+                if let Some(forwarder) = forwarder {
+                    unsafe { libc::kill(forwarder, libc::SIGKILL) };
+                }
+                return Err("could not drop privileges to supervise".to_string());
+            }
+            // the slave stays open here, as output written just before the command exits may
+            // otherwise be lost when the master sees the hangup
+            for (_, theirs) in replace {
+                if pty.is_none_or(|(_, slave)| slave != theirs) {
+                    unsafe { libc::close(theirs) };
+                }
+            }
+            SIGNAL_PIPE.store(signal_pipe[1], Ordering::Relaxed);
+            let relay = Relay {
+                ro,
+                terminal: terminal.as_ref(),
+                master: pty.map(|(master, _)| master),
+                child,
+                forwarder,
+            };
+            let outcome = relay.run(&mut channels, signal_pipe[0], recording.as_mut(), started);
+            if let Some(forwarder) = forwarder {
+                unsafe {
+                    libc::kill(forwarder, libc::SIGKILL);
+                    libc::waitpid(forwarder, std::ptr::null_mut(), 0);
+                }
+            }
+            if let Some(terminal) = &terminal {
+                terminal.restore();
+            }
+            if let Some(recording) = recording.as_mut() {
+                recording.finish(&exit_description(outcome.status));
+            }
+            Ok(Some(outcome))
+        }
+    }
+}
+
+/// with supervise, fork a process that keeps root only to send the command the signals that
+/// please, once it has given up root, passes on. its real uid is the user's, so please may signal
+/// it, and it dies with please
+fn fork_forwarder(ro: &RunOptions, child: libc::pid_t) -> Result<libc::pid_t, String> {
+    let mut signals = FORWARD_SIGNALS.to_vec();
+    signals.push(libc::SIGCONT);
+    let mut set = unsafe { std::mem::zeroed::<libc::sigset_t>() };
+    let mut previous = unsafe { std::mem::zeroed::<libc::sigset_t>() };
+    unsafe {
+        libc::sigemptyset(&mut set);
+        for signal in &signals {
+            libc::sigaddset(&mut set, *signal);
+        }
+    }
+
+    // a pidfd is taken before please can reap the command, so its pid cannot be reused
+    #[cfg(target_os = "linux")]
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, child, 0) } as RawFd;
+    #[cfg(target_os = "linux")]
+    if pidfd < 0 {
+        return Err(os_error("pidfd_open"));
+    }
+    let send = |signal: libc::c_int| -> bool {
+        #[cfg(target_os = "linux")]
+        return unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                pidfd,
+                signal,
+                std::ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        } == 0;
+        #[cfg(not(target_os = "linux"))]
+        return unsafe { libc::kill(child, signal) } == 0;
+    };
+
+    // blocked from before the fork, so none sent early are lost
+    unsafe { libc::sigprocmask(libc::SIG_BLOCK, &set, &mut previous) };
+    let parent = unsafe { libc::getpid() };
+    let forwarder = unsafe { libc::fork() };
+    if forwarder != 0 {
+        unsafe { libc::sigprocmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut()) };
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::close(pidfd)
+        };
+        if forwarder < 0 {
+            return Err(os_error("fork"));
+        }
+        return Ok(forwarder);
+    }
+
+    unsafe {
+        if !esc_privs() || libc::setreuid(ro.original_uid.as_raw(), 0) != 0 {
+            libc::_exit(1);
+        }
+        // after the change of uid, which would clear it
+        #[cfg(target_os = "linux")]
+        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+        if libc::getppid() != parent {
+            libc::_exit(0);
+        }
+        // the terminal and pipes of the command are not held open
+        #[cfg(target_os = "linux")]
+        {
+            libc::syscall(libc::SYS_close_range, 3, pidfd - 1, 0);
+            libc::syscall(libc::SYS_close_range, pidfd + 1, !0u32, 0);
+        }
+        #[cfg(not(target_os = "linux"))]
+        for fd in 3..1024 {
+            libc::close(fd);
+        }
+    }
+    loop {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+            continue;
+        }
+        if !send(signal) {
+            if std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH) {
+                unsafe { libc::_exit(0) };
+            }
+            log_action("please", "signal_fail", ro, &ro.original_command.join(" "));
+        }
+    }
+}
+
+/// install signal_handler for the signals relay acts on, without restarting system calls
+fn handle_signals() {
+    let mut signals = FORWARD_SIGNALS.to_vec();
+    signals.push(libc::SIGWINCH);
+    signals.push(libc::SIGCHLD);
+    for signal in signals {
+        unsafe {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = signal_handler as extern "C" fn(libc::c_int) as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// write all of buf to fd, false if it cannot be written
+fn write_all(fd: RawFd, mut buf: &[u8]) -> bool {
+    while !buf.is_empty() {
+        let written = unsafe { libc::write(fd, buf.as_ptr() as *const libc::c_void, buf.len()) };
+        if written < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return false;
+        }
+        buf = &buf[written as usize..];
+    }
+    true
+}
+
+/// what the parent needs to pass things on to the command
+struct Relay<'a> {
+    ro: &'a RunOptions,
+    terminal: Option<&'a Terminal>,
+    master: Option<RawFd>,
+    child: libc::pid_t,
+    /// with supervise, the process that signals the command for please, which has given up root
+    forwarder: Option<libc::pid_t>,
+}
+
+impl Relay<'_> {
+    /// pass data along channels until the command, started then, exits
+    fn run(
+        &self,
+        channels: &mut [Channel],
+        signals: RawFd,
+        mut recording: Option<&mut Recording>,
+        started: Instant,
+    ) -> Outcome {
+        handle_signals();
+        if let Some(terminal) = self.terminal {
+            terminal.raw();
+        }
+
+        let mut buf = [0u8; 4096];
+        // the command may have exited before SIGCHLD was handled
+        let mut status = self.wait_child();
+
+        loop {
+            let mut fds = vec![libc::pollfd {
+                fd: signals,
+                events: libc::POLLIN,
+                revents: 0,
+            }];
+            let open: Vec<usize> = (0..channels.len()).filter(|c| channels[*c].open).collect();
+            for c in &open {
+                fds.push(libc::pollfd {
+                    fd: channels[*c].from,
+                    events: libc::POLLIN,
+                    revents: 0,
+                });
+            }
+            // once the command has exited, output may still be on its way through the
+            // pseudo-terminal, wait a little for it, but not for anything the command left
+            // running in the background
+            let timeout = if status.is_some() { 100 } else { -1 };
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+            if ready < 0 {
+                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            if ready == 0 || (status.is_some() && open.is_empty()) {
+                break;
+            }
+
+            if fds[0].revents & libc::POLLIN != 0 {
+                let mut signal = 0u8;
+                while unsafe { libc::read(signals, &mut signal as *mut u8 as *mut libc::c_void, 1) }
+                    == 1
+                {
+                    match signal as libc::c_int {
+                        libc::SIGWINCH => self.copy_size(),
+                        libc::SIGCHLD => {
+                            if let Some(exited) = self.wait_child() {
+                                status = Some(exited);
+                            }
+                        }
+                        // the terminal sent these to the command too
+                        libc::SIGINT | libc::SIGQUIT if self.master.is_none() => {}
+                        // once reaped, the pid may be another process
+                        _ if status.is_some() => {}
+                        signal => self.signal_child(signal),
+                    }
+                }
+            }
+
+            for (pollfd, c) in fds[1..].iter().zip(open) {
+                if pollfd.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) == 0 {
+                    continue;
+                }
+                let channel = &mut channels[c];
+                let read = unsafe {
+                    libc::read(
+                        channel.from,
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                    )
+                };
+                if read <= 0 {
+                    channel.open = false;
+                    if channel.close {
+                        unsafe { libc::close(channel.to) };
+                    }
+                    continue;
+                }
+                if let Some(recording) = recording.as_mut() {
+                    recording.record(channel.stream, &buf[..read as usize]);
+                }
+                if !write_all(channel.to, &buf[..read as usize]) && channel.stream == 'i' {
+                    channel.open = false;
+                }
+            }
+        }
+
+        let (status, rusage, ended) = match status {
+            Some(exited) => exited,
+            None => loop {
+                let mut status = 0;
+                let mut rusage = unsafe { std::mem::zeroed::<libc::rusage>() };
+                if unsafe { libc::wait4(self.child, &mut status, 0, &mut rusage) } == self.child {
+                    break (status, rusage, Instant::now());
+                }
+            },
+        };
+        Outcome {
+            status,
+            duration: ended.duration_since(started),
+            rusage,
+        }
+    }
+
+    /// give the pseudo-terminal the size of the user's terminal
+    fn copy_size(&self) {
+        if let (Some(terminal), Some(master)) = (self.terminal, self.master) {
+            terminal.copy_size(master);
+        }
+    }
+
+    /// send a signal to the command, which runs as another user
+    fn signal_child(&self, signal: libc::c_int) {
+        let sent = match self.forwarder {
+            Some(forwarder) => (unsafe { libc::kill(forwarder, signal) }) == 0,
+            None => {
+                esc_privs();
+                let sent = unsafe { libc::kill(self.child, signal) } == 0;
+                drop_privs(self.ro);
+                sent
+            }
+        };
+        if !sent {
+            log_action(
+                "please",
+                "signal_fail",
+                self.ro,
+                &self.ro.original_command.join(" "),
+            );
+        }
+    }
+
+    /// reap the command if it has exited. if it stopped on its pseudo-terminal, stop please too,
+    /// then continue both. without one the terminal stops and continues them together
+    fn wait_child(&self) -> Option<(libc::c_int, libc::rusage, Instant)> {
+        let mut status = 0;
+        let mut rusage = unsafe { std::mem::zeroed::<libc::rusage>() };
+        let options = match self.master {
+            Some(_) => libc::WNOHANG | libc::WUNTRACED,
+            None => libc::WNOHANG,
+        };
+        if unsafe { libc::wait4(self.child, &mut status, options, &mut rusage) } != self.child {
+            return None;
+        }
+        if libc::WIFSTOPPED(status) {
+            if let Some(terminal) = self.terminal {
+                terminal.restore();
+            }
+            unsafe { libc::kill(libc::getpid(), libc::SIGSTOP) };
+            if let Some(terminal) = self.terminal {
+                terminal.raw();
+            }
+            self.copy_size();
+            self.signal_child(libc::SIGCONT);
+            return None;
+        }
+        Some((status, rusage, Instant::now()))
+    }
+}
+
+/// how the command exited, for the session info
+fn exit_description(status: libc::c_int) -> String {
+    if libc::WIFSIGNALED(status) {
+        return format!("signal {}", libc::WTERMSIG(status));
+    }
+    libc::WEXITSTATUS(status).to_string()
+}
@@ -0,0 +1,298 @@
+//    please
+//    Copyright (C) 2020-2021 ed neville
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! record what is typed and printed while a command runs, to be replayed by pleasereplay
+//!
+//! each session is a directory holding info, key=value lines about the command, data, every
+//! stream one after another, and timing, a line for each piece of data giving its stream, i for
+//! input, o for output or e for errors, the seconds since the piece before and its length
+
+use crate::{prng_alpha_num_string, tty_name, RunOptions};
+use chrono::Utc;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// return the directory that sessions are recorded in
+pub fn session_dir() -> String {
+    "/var/log/please/sessions".to_string()
+}
+
+/// a session being recorded
+pub struct Recording {
+    pub id: String,
+    info: File,
+    data: File,
+    timing: File,
+    last: Instant,
+}
+
+fn escape_info(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_info(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// create the directory for the session in base, readable only by root, and its info. it is
+/// named by the date and a random string, the id that is logged
+pub fn create_session(base: &str, ro: &RunOptions) -> std::io::Result<Recording> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(base)?;
+
+    let date = Utc::now().naive_utc();
+    let id = format!(
+        "{}-{}",
+        date.format("%Y%m%d%H%M%S"),
+        prng_alpha_num_string(8)
+    );
+    let dir = format!("{}/{}", base, id);
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let open = |name: &str| {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(format!("{}/{}", dir, name))
+    };
+
+    let cwd = match std::env::current_dir() {
+        Err(_) => "unable to get cwd".to_string(),
+        Ok(x) => x.to_string_lossy().to_string(),
+    };
+    let section = match &ro.env_options {
+        Some(env_options) => format!("{}:{}", env_options.file_name, env_options.section),
+        None => "".to_string(),
+    };
+    let mut info = open("info")?;
+    for (key, value) in [
+        ("user", ro.name.as_str()),
+        ("target", ro.target.as_str()),
+        ("command", &ro.original_command.join(" ")),
+        ("date", &date.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+        ("hostname", &ro.hostname),
+        ("cwd", &cwd),
+        ("tty", &tty_name().unwrap_or_else(|| "no_tty".to_string())),
+        ("section", &section),
+    ] {
+        writeln!(info, "{}={}", key, escape_info(value))?;
+    }
+
+    // kept open, as please has given up root by the time the command exits
+    Ok(Recording {
+        id,
+        info,
+        data: open("data")?,
+        timing: open("timing")?,
+        last: Instant::now(),
+    })
+}
+
+impl Recording {
+    /// record that buf went by on stream
+    pub fn record(&mut self, stream: char, buf: &[u8]) {
+        let now = Instant::now();
+        let delay = now.duration_since(self.last);
+        self.last = now;
+        // the command goes on if the disk is full
+        let _ = self.data.write_all(buf);
+        let _ = writeln!(
+            self.timing,
+            "{} {}.{:06} {}",
+            stream,
+            delay.as_secs(),
+            delay.subsec_micros(),
+            buf.len()
+        );
+    }
+
+    /// add how the command exited to the info
+    pub fn finish(&mut self, exit: &str) {
+        let _ = writeln!(self.info, "exit={}", exit);
+    }
+}
+
+/// what the info of a session says
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionInfo {
+    pub id: String,
+    pub user: String,
+    pub target: String,
+    pub command: String,
+    pub date: String,
+    pub hostname: String,
+    pub cwd: String,
+    pub tty: String,
+    pub section: String,
+    pub exit: String,
+}
+
+/// read the info of session id in base
+pub fn read_session_info(base: &str, id: &str) -> std::io::Result<SessionInfo> {
+    let mut info = SessionInfo {
+        id: id.to_string(),
+        ..Default::default()
+    };
+    let file = File::open(format!("{}/{}/info", base, id))?;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key, unescape_info(value)),
+            None => continue,
+        };
+        match key {
+            "user" => info.user = value,
+            "target" => info.target = value,
+            "command" => info.command = value,
+            "date" => info.date = value,
+            "hostname" => info.hostname = value,
+            "cwd" => info.cwd = value,
+            "tty" => info.tty = value,
+            "section" => info.section = value,
+            "exit" => info.exit = value,
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// the sessions in base, oldest first, that match user, target and a date of YYYYmmdd
+pub fn list_sessions(
+    base: &str,
+    user: Option<&str>,
+    target: Option<&str>,
+    date: Option<&str>,
+) -> std::io::Result<Vec<SessionInfo>> {
+    let mut sessions = vec![];
+    for entry in fs::read_dir(base)? {
+        let id = entry?.file_name().to_string_lossy().to_string();
+        let info = match read_session_info(base, &id) {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        if user.is_some_and(|user| user != info.user)
+            || target.is_some_and(|target| target != info.target)
+            || date.is_some_and(|date| !id.starts_with(date))
+        {
+            continue;
+        }
+        sessions.push(info);
+    }
+    sessions.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(sessions)
+}
+
+/// a piece of a recorded session
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionEvent {
+    pub stream: char,
+    pub delay: Duration,
+    pub data: Vec<u8>,
+}
+
+/// read the pieces of session id in base
+pub fn read_session(base: &str, id: &str) -> std::io::Result<Vec<SessionEvent>> {
+    let bad = |line: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("bad timing line: {}", line),
+        )
+    };
+    let timing = File::open(format!("{}/{}/timing", base, id))?;
+    let mut data = BufReader::new(File::open(format!("{}/{}/data", base, id))?);
+
+    let mut events = vec![];
+    for line in BufReader::new(timing).lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (stream, delay, len) = match fields[..] {
+            [stream, delay, len] if stream.len() == 1 => (
+                stream.chars().next().unwrap(),
+                delay.parse::<f64>().map_err(|_| bad(&line))?,
+                len.parse::<u64>().map_err(|_| bad(&line))?,
+            ),
+            _ => return Err(bad(&line)),
+        };
+        let delay = Duration::try_from_secs_f64(delay).map_err(|_| bad(&line))?;
+        // the length is only trusted as far as the data goes
+        let mut buf = vec![];
+        data.by_ref().take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("data ends before timing line: {}", line),
+            ));
+        }
+        events.push(SessionEvent {
+            stream,
+            delay,
+            data: buf,
+        });
+    }
+    Ok(events)
+}
+
+/// write the output and errors of events to out, waiting as long as when they were recorded
+/// divided by speed, though never longer than max_wait. with input the input is written too
+pub fn replay(
+    events: &[SessionEvent],
+    speed: f64,
+    max_wait: Option<Duration>,
+    input: bool,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    for event in events {
+        if event.stream == 'i' && !input {
+            continue;
+        }
+        let mut delay = event.delay.div_f64(speed);
+        if let Some(max_wait) = max_wait {
+            delay = delay.min(max_wait);
+        }
+        std::thread::sleep(delay);
+        out.write_all(&event.data)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// true if id may be the name of a session in base, rather than a path out of it
+pub fn valid_session_id(base: &str, id: &str) -> bool {
+    !id.is_empty()
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && Path::new(&format!("{}/{}", base, id)).is_dir()
+}
@@ -0,0 +1,822 @@
+//    please
+//    Copyright (C) 2020-2021 ed neville
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! convert common sudoers syntax into please.ini sections
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// tags that change nothing when translated to please
+const QUIET_TAGS: [&str; 4] = ["PASSWD", "NOSETENV", "NOMAIL", "NOINTERCEPT"];
+
+/// tags that please has no equivalent for
+const LOUD_TAGS: [&str; 5] = ["SETENV", "MAIL", "FOLLOW", "NOFOLLOW", "INTERCEPT"];
+
+const ALIAS_TYPES: [&str; 5] = [
+    "User_Alias",
+    "Runas_Alias",
+    "Host_Alias",
+    "Cmnd_Alias",
+    "Cmd_Alias",
+];
+
+/// a logical sudoers line, continuations joined and comments removed
+struct SudoersLine {
+    number: usize,
+    text: String,
+}
+
+/// Defaults that have a please equivalent
+#[derive(Default)]
+struct SudoersDefaults {
+    env_keep: Vec<String>,
+    secure_path: Option<String>,
+    token_timeout: Option<u64>,
+    timeout: Option<u32>,
+    use_pty: Option<bool>,
+    record: Option<bool>,
+}
+
+#[derive(Default)]
+struct SudoersAliases {
+    user: HashMap<String, Vec<String>>,
+    runas: HashMap<String, Vec<String>>,
+    host: HashMap<String, Vec<String>>,
+    cmnd: HashMap<String, Vec<String>>,
+}
+
+/// state carried along a command list, runas and tags apply to the commands that follow
+#[derive(Clone, Default)]
+struct CmndState {
+    runas_users: Option<Vec<String>>,
+    runas_groups: Option<Vec<String>>,
+    nopasswd: bool,
+    noexec: bool,
+    record: Option<bool>,
+}
+
+/// a section being written, keys are in output order
+struct Section {
+    keys: Vec<(&'static str, String)>,
+}
+
+impl Section {
+    fn set(&mut self, key: &'static str, value: &str) {
+        self.keys.push((key, value.to_string()));
+    }
+}
+
+/// convert sudoers to please.ini, anything that is not translated is left as a comment
+pub fn sudoers_to_ini(sudoers: &str, source: &str) -> String {
+    convert_sudoers(sudoers, source).0
+}
+
+/// as sudoers_to_ini, also returning the lines that could not be converted exactly. these are
+/// left commented out with an error
+pub fn convert_sudoers(sudoers: &str, source: &str) -> (String, Vec<String>) {
+    let lines = sudoers_lines(sudoers);
+    let mut aliases = SudoersAliases::default();
+    let mut defaults = SudoersDefaults::default();
+    let mut warnings: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut refused: HashMap<usize, String> = HashMap::new();
+
+    let prefix = match Path::new(source).file_name() {
+        Some(name) => name
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect(),
+        None => "sudoers".to_string(),
+    };
+
+    // Defaults and aliases apply wherever they are in the file, so are read first
+    for line in &lines {
+        let w = warnings.entry(line.number).or_default();
+        let keyword = line.text.split_whitespace().next().unwrap_or("");
+
+        if keyword.starts_with("Defaults") {
+            parse_defaults(&line.text, &mut defaults, w);
+        } else if ALIAS_TYPES.contains(&keyword) {
+            if let Err(error) = parse_alias(&line.text, &mut aliases) {
+                refused.insert(line.number, error);
+            }
+        }
+    }
+
+    let mut out = vec![format!("# converted from {} by sudoers2please", source)];
+    let mut errors = vec![];
+
+    for line in &lines {
+        let keyword = line.text.split_whitespace().next().unwrap_or("");
+        let mut w = warnings.remove(&line.number).unwrap_or_default();
+        let mut error = refused.remove(&line.number);
+        let mut sections = vec![];
+
+        if keyword.starts_with("#include") || keyword.starts_with("@include") {
+            w.push(format!(
+                "includes are not followed, convert them separately: {}",
+                line.text
+            ));
+        } else if !keyword.starts_with("Defaults") && !ALIAS_TYPES.contains(&keyword) {
+            match user_spec_sections(&line.text, &aliases, &defaults, &mut w) {
+                Ok(converted) => sections = converted,
+                Err(x) => error = Some(x),
+            }
+        }
+
+        let warned = !w.is_empty() || error.is_some();
+        if warned && out.len() > 1 {
+            out.push(String::new());
+        }
+        for warning in w {
+            out.push(format!("# warning: line {}: {}", line.number, warning));
+        }
+        if let Some(error) = error {
+            out.push(format!("# error: line {}: {}", line.number, error));
+            out.push(format!("# {}", line.text));
+            errors.push(format!("line {}: {}", line.number, error));
+        }
+
+        for (count, section) in sections.iter().enumerate() {
+            if count > 0 || !warned {
+                out.push(String::new());
+            }
+            if count == 0 {
+                out.push(format!("[{}_{}]", prefix, line.number));
+            } else {
+                out.push(format!("[{}_{}_{}]", prefix, line.number, count + 1));
+            }
+            for (key, value) in &section.keys {
+                out.push(format!("{} = {}", key, value));
+            }
+        }
+    }
+
+    out.push(String::new());
+    (out.join("\n"), errors)
+}
+
+/// join continuation lines and remove comments, keeping the first line number
+fn sudoers_lines(sudoers: &str) -> Vec<SudoersLine> {
+    let mut lines = vec![];
+    let mut text = String::new();
+    let mut start = 0;
+
+    for (number, l) in sudoers.split('\n').enumerate() {
+        if text.is_empty() {
+            start = number + 1;
+        }
+
+        if let Some(l) = l.strip_suffix('\\') {
+            text.push_str(l);
+            text.push(' ');
+            continue;
+        }
+        text.push_str(l);
+
+        let stripped = strip_comment(&text);
+        if !stripped.is_empty() {
+            lines.push(SudoersLine {
+                number: start,
+                text: stripped,
+            });
+        }
+        text.clear();
+    }
+
+    lines
+}
+
+/// a # starts a comment unless it is an include or a uid such as #1000
+fn strip_comment(text: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.starts_with("#include") {
+        return trimmed.to_string();
+    }
+
+    let chars: Vec<char> = trimmed.chars().collect();
+    for (pos, c) in chars.iter().enumerate() {
+        if *c == '#' && !chars.get(pos + 1).is_some_and(|n| n.is_ascii_digit()) {
+            return chars[..pos].iter().collect::<String>().trim().to_string();
+        }
+    }
+    trimmed.to_string()
+}
+
+/// split on sep when not escaped, quoted or within brackets
+fn split_list(text: &str, sep: char) -> Vec<String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if escaped {
+            item.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if c == sep && depth == 0 && !quoted => {
+                items.push(item.trim().to_string());
+                item.clear();
+                continue;
+            }
+            _ => {}
+        }
+        item.push(c);
+    }
+    items.push(item.trim().to_string());
+
+    items.into_iter().filter(|i| !i.is_empty()).collect()
+}
+
+/// split on sep when it is not escaped with a backslash, which is kept
+fn split_unescaped(text: &str, sep: char) -> Vec<String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if c == sep && !escaped {
+            items.push(std::mem::take(&mut item));
+            continue;
+        }
+        escaped = c == '\\' && !escaped;
+        item.push(c);
+    }
+    items.push(item);
+
+    items
+}
+
+/// read a Defaults line, only unscoped Defaults are translated
+fn parse_defaults(text: &str, defaults: &mut SudoersDefaults, warnings: &mut Vec<String>) {
+    let (keyword, rest) = match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], text[pos..].trim()),
+        None => (text, ""),
+    };
+
+    if keyword != "Defaults" {
+        warnings.push(format!("scoped Defaults are not translated: {}", text));
+        return;
+    }
+
+    for entry in split_list(rest, ',') {
+        let (name, op, value) = match entry.find('=') {
+            Some(pos) => {
+                let (name, op) = match entry[..pos].strip_suffix(['+', '-']) {
+                    Some(name) => (name, &entry[pos - 1..=pos]),
+                    None => (&entry[..pos], "="),
+                };
+                (
+                    name.trim(),
+                    op,
+                    entry[pos + 1..].trim().trim_matches('"').to_string(),
+                )
+            }
+            None => (entry.as_str(), "", String::new()),
+        };
+
+        match (name, op) {
+            ("env_keep", _) => {
+                let vars: Vec<String> = value.split_whitespace().map(|v| v.to_string()).collect();
+                match op {
+                    "+=" => defaults.env_keep.extend(vars),
+                    "-=" => defaults.env_keep.retain(|v| !vars.contains(v)),
+                    _ => defaults.env_keep = vars,
+                }
+            }
+            ("secure_path", "=") => defaults.secure_path = Some(value),
+            ("timestamp_timeout", "=") => match value.parse::<f64>() {
+                Ok(minutes) if minutes >= 0.0 => {
+                    defaults.token_timeout = Some((minutes * 60.0) as u64)
+                }
+                _ => warnings.push(format!("timestamp_timeout is not translated: {}", value)),
+            },
+            ("passwd_timeout", "=") => match value.parse::<f64>() {
+                Ok(minutes) if minutes > 0.0 => defaults.timeout = Some((minutes * 60.0) as u32),
+                Ok(_) => {}
+                _ => warnings.push(format!("passwd_timeout is not translated: {}", value)),
+            },
+            ("use_pty", "") => defaults.use_pty = Some(true),
+            ("!use_pty", "") => defaults.use_pty = Some(false),
+            // please records input and output together
+            ("log_input", "") | ("log_output", "") => defaults.record = Some(true),
+            ("!log_input", "") | ("!log_output", "") => defaults.record = Some(false),
+            // please always starts with a clean environment
+            ("env_reset", "") => {}
+            _ => warnings.push(format!("Defaults {} is not translated", entry)),
+        }
+    }
+}
+
+/// read an alias line, which may hold several definitions separated by :
+fn parse_alias(text: &str, aliases: &mut SudoersAliases) -> Result<(), String> {
+    let (keyword, rest) = match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], &text[pos..]),
+        None => return Err(format!("could not parse alias: {}", text)),
+    };
+
+    let map = match keyword {
+        "User_Alias" => &mut aliases.user,
+        "Runas_Alias" => &mut aliases.runas,
+        "Host_Alias" => &mut aliases.host,
+        _ => &mut aliases.cmnd,
+    };
+
+    // a : that is not escaped separates definitions, each of which is NAME =
+    let mut definitions = vec![];
+    for definition in split_unescaped(rest, ':') {
+        let pos = definition.find('=').filter(|pos| {
+            let name = definition[..*pos].trim();
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        });
+        match pos {
+            Some(pos) => definitions.push((
+                definition[..pos].trim().to_string(),
+                split_list(&definition[pos + 1..], ','),
+            )),
+            None => {
+                return Err(format!(
+                    "could not parse alias: {}, a : within a command is written \\:",
+                    definition.trim()
+                ))
+            }
+        }
+    }
+
+    map.extend(definitions);
+    Ok(())
+}
+
+/// expand alias names found in items, returning the members
+fn resolve_aliases(
+    items: &[String],
+    aliases: &HashMap<String, Vec<String>>,
+    warnings: &mut Vec<String>,
+    depth: usize,
+) -> Vec<String> {
+    let mut resolved = vec![];
+
+    for item in items {
+        match aliases.get(item) {
+            Some(_) if depth > 16 => {
+                warnings.push(format!("alias {} is nested too deeply", item));
+            }
+            Some(members) => {
+                resolved.extend(resolve_aliases(members, aliases, warnings, depth + 1));
+            }
+            None => resolved.push(item.to_string()),
+        }
+    }
+
+    resolved
+}
+
+/// a regex matching any of the items, None when ALL is one of them
+fn regex_list(items: &[String]) -> Option<String> {
+    if items.iter().any(|i| i == "ALL") {
+        return None;
+    }
+
+    let escaped: Vec<String> = items.iter().map(|i| regex::escape(i)).collect();
+    if escaped.len() == 1 {
+        return Some(escaped[0].clone());
+    }
+    Some(format!("({})", escaped.join("|")))
+}
+
+/// translate a sudoers glob to a regex, * and ? do not cross / within a path
+fn glob_regex(glob: &str, path: bool) -> String {
+    let mut re = String::new();
+    let mut chars = glob.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if path => re.push_str("[^/]*"),
+            '*' => re.push_str(".*"),
+            '?' if path => re.push_str("[^/]"),
+            '?' => re.push('.'),
+            '[' => {
+                re.push('[');
+                for (pos, c) in chars.by_ref().enumerate() {
+                    match c {
+                        '!' if pos == 0 => re.push('^'),
+                        ']' => break,
+                        '\\' => re.push_str(r"\\"),
+                        _ => re.push(c),
+                    }
+                }
+                re.push(']');
+            }
+            // please escapes spaces within arguments with a backslash
+            '\\' => match chars.next() {
+                Some(' ') => re.push_str(r"\\ "),
+                Some(n) => re.push_str(&regex::escape(&n.to_string())),
+                None => re.push_str(r"\\"),
+            },
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    re
+}
+
+/// the rule regex for a command, a command without arguments permits any arguments
+fn command_rule(command: &str) -> String {
+    if command == "ALL" {
+        return ".*".to_string();
+    }
+
+    let (path, args) = match command.find(char::is_whitespace) {
+        Some(pos) => (&command[..pos], Some(command[pos..].trim())),
+        None => (command, None),
+    };
+
+    let mut rule = if path.starts_with('^') && path.ends_with('$') {
+        path[1..path.len() - 1].to_string()
+    } else if path.ends_with('/') {
+        format!("{}[^/]+", glob_regex(path, true))
+    } else {
+        glob_regex(path, true)
+    };
+
+    match args {
+        None => rule.push_str("( .*)?"),
+        Some("\"\"") => {}
+        Some(args) if args.starts_with('^') && args.ends_with('$') => {
+            rule.push(' ');
+            rule.push_str(&args[1..args.len() - 1]);
+        }
+        Some(args) => {
+            rule.push(' ');
+            rule.push_str(&glob_regex(args, false));
+        }
+    }
+
+    rule
+}
+
+fn is_tag(word: &str) -> bool {
+    [
+        "NOPASSWD",
+        "NOEXEC",
+        "EXEC",
+        "LOG_INPUT",
+        "LOG_OUTPUT",
+        "NOLOG_INPUT",
+        "NOLOG_OUTPUT",
+    ]
+    .contains(&word)
+        || QUIET_TAGS.contains(&word)
+        || LOUD_TAGS.contains(&word)
+}
+
+/// the leading TAG: of a command, if there is one
+fn leading_tag(text: &str) -> Option<(&str, &str)> {
+    let pos = text.find(':')?;
+    let tag = &text[..pos];
+    if is_tag(tag) {
+        return Some((tag, text[pos + 1..].trim_start()));
+    }
+    None
+}
+
+/// the leading OPTION=value of a command, if there is one
+fn leading_option(text: &str) -> Option<(&str, &str, &str)> {
+    let pos = text.find('=')?;
+    let option = &text[..pos];
+    if option.is_empty() || !option.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        return None;
+    }
+    let rest = &text[pos + 1..];
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    Some((option, &rest[..end], rest[end..].trim_start()))
+}
+
+/// split a user specification's command list at the : that start new host lists, a \\: is
+/// part of a command
+fn split_host_specs(text: &str) -> Vec<String> {
+    let mut specs = vec![];
+    let mut spec = String::new();
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if escaped {
+            spec.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ':' if depth == 0 => {
+                let word = spec
+                    .rsplit(|c: char| c.is_whitespace() || c == ',' || c == ')')
+                    .next()
+                    .unwrap_or("");
+                if !is_tag(word) {
+                    specs.push(spec.trim().to_string());
+                    spec.clear();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        spec.push(c);
+    }
+    specs.push(spec.trim().to_string());
+
+    specs
+}
+
+/// sections for a user specification such as: %admin web1 = (root) NOPASSWD: /bin/ls
+fn user_spec_sections(
+    text: &str,
+    aliases: &SudoersAliases,
+    defaults: &SudoersDefaults,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Section>, String> {
+    let mut sections = vec![];
+
+    let equals = match text.find('=') {
+        Some(pos) => pos,
+        None => return Err(format!("could not parse: {}", text)),
+    };
+
+    let head: Vec<String> = text[..equals]
+        .split(',')
+        .map(|s| s.trim())
+        .collect::<Vec<&str>>()
+        .join(",")
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+    if head.len() != 2 {
+        return Err(format!("could not parse: {}", text));
+    }
+
+    let mut users = vec![];
+    let mut groups = vec![];
+    for user in resolve_aliases(&split_list(&head[0], ','), &aliases.user, warnings, 0) {
+        if user.starts_with('!')
+            || user.starts_with('#')
+            || user.starts_with('+')
+            || user.starts_with("%:")
+            || user.starts_with("%#")
+        {
+            warnings.push(format!("user {} is not translated", user));
+        } else if let Some(group) = user.strip_prefix('%') {
+            groups.push(group.to_string());
+        } else {
+            users.push(user);
+        }
+    }
+
+    let mut host_specs = vec![(head[1].clone(), text[equals + 1..].to_string())];
+    let mut rest = split_host_specs(&text[equals + 1..]);
+    if rest.is_empty() {
+        return Err(format!("could not parse: {}", text));
+    }
+    host_specs[0].1 = rest.remove(0);
+    for spec in rest {
+        match spec.find('=') {
+            Some(pos) => {
+                host_specs.push((spec[..pos].trim().to_string(), spec[pos + 1..].to_string()))
+            }
+            None => {
+                return Err(format!(
+                    "could not parse: {}, a : within a command is written \\:",
+                    spec
+                ))
+            }
+        }
+    }
+
+    for (hosts, commands) in host_specs {
+        let hosts = resolve_aliases(&split_list(&hosts, ','), &aliases.host, warnings, 0);
+        let hostname = regex_list(&hosts);
+
+        let mut state = CmndState::default();
+        for command in split_list(&commands, ',') {
+            let mut command = command.as_str();
+            let mut options = vec![];
+
+            if command.starts_with('(') {
+                let end = match command.find(')') {
+                    Some(end) => end,
+                    None => return Err(format!("could not parse: {}", command)),
+                };
+                let runas = &command[1..end];
+                let (runas_users, runas_groups) = match runas.find(':') {
+                    Some(pos) => (&runas[..pos], Some(&runas[pos + 1..])),
+                    None => (runas, None),
+                };
+                state.runas_users = match runas_users.trim() {
+                    "" if runas_groups.is_some() => None,
+                    "" => Some(vec!["root".to_string()]),
+                    users => Some(resolve_aliases(
+                        &split_list(users, ','),
+                        &aliases.runas,
+                        warnings,
+                        0,
+                    )),
+                };
+                state.runas_groups = runas_groups.map(|g| split_list(g, ','));
+                command = command[end + 1..].trim_start();
+            }
+
+            loop {
+                if let Some((tag, rest)) = leading_tag(command) {
+                    match tag {
+                        "NOPASSWD" => state.nopasswd = true,
+                        "PASSWD" => state.nopasswd = false,
+                        "NOEXEC" => state.noexec = true,
+                        "EXEC" => state.noexec = false,
+                        "LOG_INPUT" | "LOG_OUTPUT" => state.record = Some(true),
+                        "NOLOG_INPUT" | "NOLOG_OUTPUT" => state.record = Some(false),
+                        _ if LOUD_TAGS.contains(&tag) => {
+                            warnings.push(format!("{} is not translated", tag))
+                        }
+                        _ => {}
+                    }
+                    command = rest;
+                    continue;
+                }
+                if let Some((option, value, rest)) = leading_option(command) {
+                    options.push((option, value));
+                    command = rest;
+                    continue;
+                }
+                break;
+            }
+
+            let (permit, command) = match command.strip_prefix('!') {
+                Some(command) => (false, command.trim_start()),
+                None => (true, command),
+            };
+
+            for command in resolve_aliases(&[command.to_string()], &aliases.cmnd, warnings, 0) {
+                let (permit, command) = match command.strip_prefix('!') {
+                    Some(command) => (!permit, command.trim_start().to_string()),
+                    None => (permit, command),
+                };
+
+                let (edit, rule) = if let Some(files) = command.strip_prefix("sudoedit ") {
+                    let files: Vec<String> = files
+                        .split_whitespace()
+                        .map(|f| glob_regex(f, true))
+                        .collect();
+                    if files.len() == 1 {
+                        (true, files[0].clone())
+                    } else {
+                        (true, format!("({})", files.join("|")))
+                    }
+                } else if command == "ALL" || command.starts_with('/') || command.starts_with('^') {
+                    (false, command_rule(&command))
+                } else {
+                    warnings.push(format!("command {} is not translated", command));
+                    continue;
+                };
+
+                for (name, group) in [(&users, false), (&groups, true)] {
+                    if name.is_empty() {
+                        continue;
+                    }
+
+                    for (target, target_group) in runas_targets(&state) {
+                        let mut section = Section { keys: vec![] };
+                        section.set(
+                            "name",
+                            &regex_list(name).unwrap_or_else(|| ".*".to_string()),
+                        );
+                        if group {
+                            section.set("group", "true");
+                        }
+                        if let Some(hostname) = &hostname {
+                            section.set("hostname", hostname);
+                        }
+                        section.set("target", &target);
+                        if let Some(target_group) = target_group {
+                            section.set("target_group", &target_group);
+                        }
+                        if edit {
+                            section.set("type", "edit");
+                        }
+                        section.set("rule", &rule);
+                        if !permit {
+                            section.set("permit", "false");
+                        }
+                        if state.nopasswd {
+                            section.set("require_pass", "false");
+                        }
+                        if state.noexec && !edit {
+                            section.set("noexec", "true");
+                        }
+                        if let Some(record) = state.record.or(defaults.record) {
+                            if !edit {
+                                section.set("record", &record.to_string());
+                            }
+                        }
+                        for (option, value) in &options {
+                            match sudoers_date(value) {
+                                Some(date) if *option == "NOTBEFORE" => {
+                                    section.set("notbefore", &date)
+                                }
+                                Some(date) if *option == "NOTAFTER" => {
+                                    section.set("notafter", &date)
+                                }
+                                _ => {
+                                    warnings.push(format!("{}={} is not translated", option, value))
+                                }
+                            }
+                        }
+                        set_defaults(&mut section, defaults, edit);
+                        sections.push(section);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(sections)
+}
+
+/// target and target_group pairs for a runas spec, a group spec adds a section that needs --group
+fn runas_targets(state: &CmndState) -> Vec<(String, Option<String>)> {
+    let target = match &state.runas_users {
+        Some(users) => regex_list(users).unwrap_or_else(|| ".*".to_string()),
+        None if state.runas_groups.is_some() => "%{USER}".to_string(),
+        None => "root".to_string(),
+    };
+
+    let mut targets = vec![];
+    if state.runas_users.is_some() || state.runas_groups.is_none() {
+        targets.push((target.clone(), None));
+    }
+    if let Some(groups) = &state.runas_groups {
+        targets.push((
+            target,
+            Some(regex_list(groups).unwrap_or_else(|| ".*".to_string())),
+        ));
+    }
+    targets
+}
+
+/// sudoers generalized time YYYYmmddHHMMSSZ as a please date
+fn sudoers_date(value: &str) -> Option<String> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    match digits.len() {
+        8 | 14 => Some(digits),
+        12 => Some(format!("{}00", digits)),
+        10 => Some(format!("{}0000", digits)),
+        _ => None,
+    }
+}
+
+/// apply the translated Defaults to a section
+fn set_defaults(section: &mut Section, defaults: &SudoersDefaults, edit: bool) {
+    if !defaults.env_keep.is_empty() {
+        section.set("permit_env", &regex_list(&defaults.env_keep).unwrap());
+    }
+    if let Some(secure_path) = &defaults.secure_path {
+        if !edit {
+            section.set("search_path", secure_path);
+        }
+    }
+    if let Some(token_timeout) = defaults.token_timeout {
+        section.set("token_timeout", &token_timeout.to_string());
+    }
+    if let Some(timeout) = defaults.timeout {
+        section.set("timeout", &timeout.to_string());
+    }
+    if let Some(use_pty) = defaults.use_pty {
+        if !edit {
+            section.set("use_pty", &use_pty.to_string());
+        }
+    }
+}
@@ -5,8 +5,19 @@ mod basic_ro;
 #[cfg(test)]
 mod test {
     use super::*;
+    use base64::Engine;
     use basic_ro::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::unistd::{fork, ForkResult};
+    use pleaser::record::*;
+    use pleaser::sudoers::*;
     use pleaser::*;
+    use std::ffi::CStr;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
     #[test]
     fn test_execute_config() {
@@ -1609,4 +1620,3694 @@ rule = /etc/fstab
 
         assert_eq!(can(&vec_eo, &mut ro).edit_mode, None);
     }
+
+    #[test]
+    fn test_regex_cache_compiled_at_parse() {
+        let config = "
+[ed]
+name = %{USER}
+rule = /bin/bash
+"
+        .to_string();
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        let cache = vec_eo[0].regex_cache.as_ref().unwrap();
+        assert_eq!(cache.user, "ed");
+        assert_eq!(cache.patterns.get("%{USER}").unwrap().as_str(), "^ed$");
+        assert!(cache.patterns.contains_key("/bin/bash"));
+        // the default target is compiled too
+        assert!(cache.patterns.contains_key("root"));
+
+        assert!(can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_regex_cache_is_used() {
+        let config = "
+[ed]
+name = ed
+rule = /bin/bash
+"
+        .to_string();
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/sh");
+        let mut vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        vec_eo[0].regex_cache.as_mut().unwrap().patterns.insert(
+            "/bin/bash".to_string(),
+            regex::Regex::new("^/bin/sh$").unwrap(),
+        );
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        // a different user is not bound to the cache, so the rule is rebuilt
+        vec_eo[0].name = Some("%{USER}".to_string());
+        ro.name = "ned".to_string();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_regex_size_limit() {
+        let config = "
+[ed]
+name = ed
+rule = (/bin/bash.{1000}){1000}
+"
+        .to_string();
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadRegex);
+    }
+
+    #[test]
+    fn test_alias_members() {
+        let config = "[alias:web_admins]
+members = alice|bob
+
+[alias:web_hosts]
+members = web\\d+
+
+[alias:web_cmds]
+members = /bin/cat /etc/apache2/(apache2|ports)\\.conf|/bin/ls /var/www
+
+[web]
+name = %{alias:web_admins}
+hostname = %{alias:web_hosts}
+target = root
+rule = %{alias:web_cmds}
+"
+        .to_string();
+
+        let mut ro = basic_ro("alice", "root");
+        ro.hostname = "web01".to_string();
+        basic_cmd(&mut ro, "/bin/cat /etc/apache2/ports.conf");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(vec_eo.len(), 1);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        basic_cmd(&mut ro, "/bin/ls /var/www");
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        basic_cmd(&mut ro, "/bin/cat /etc/shadow");
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("carol", "root");
+        ro.hostname = "web01".to_string();
+        basic_cmd(&mut ro, "/bin/cat /etc/apache2/ports.conf");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("bob", "root");
+        ro.hostname = "db01".to_string();
+        basic_cmd(&mut ro, "/bin/cat /etc/apache2/ports.conf");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_alias_nested() {
+        let config = "[alias:dbas]
+members = carol|dave
+
+[alias:admins]
+members = alice|%{alias:dbas}
+
+[admins]
+name = %{alias:admins}
+rule = /bin/bash
+"
+        .to_string();
+
+        let mut ro = basic_ro("dave", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(vec_eo[0].name, Some("(?:alice|(?:carol|dave))".to_string()));
+        assert!(can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_alias_undefined() {
+        let config = "[admins]
+name = %{alias:admins}
+rule = /bin/bash
+
+[alias:admins]
+members = alice
+"
+        .to_string();
+
+        let ro = basic_ro("alice", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Alias);
+        assert_eq!(errors[0].line, Some(2));
+        assert_eq!(errors[0].key, Some("name".to_string()));
+    }
+
+    #[test]
+    fn test_alias_recursive() {
+        let config = "[alias:admins]
+members = alice|%{alias:admins}
+"
+        .to_string();
+
+        let ro = basic_ro("alice", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::AliasLoop);
+        assert_eq!(errors[0].section, Some("alias:admins".to_string()));
+    }
+
+    #[test]
+    fn test_alias_unknown_key() {
+        let config = "[alias:admins]
+members = alice
+rule = /bin/bash
+"
+        .to_string();
+
+        let ro = basic_ro("alice", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn test_alias_expand() {
+        let mut aliases = HashMap::new();
+        aliases.insert("a".to_string(), "x|y".to_string());
+
+        assert_eq!(
+            alias_expand("^%{alias:a}$", &aliases),
+            Ok("^(?:x|y)$".to_string())
+        );
+        assert_eq!(alias_expand("%{USER}", &aliases), Ok("%{USER}".to_string()));
+        assert_eq!(alias_expand("%{alias:b}", &aliases), Err("b".to_string()));
+        assert_eq!(alias_expand("%{alias:a", &aliases), Err("a".to_string()));
+    }
+
+    fn args_cmd(ro: &mut RunOptions, args: &[&str]) {
+        ro.new_args = args.iter().map(|s| s.to_string()).collect();
+    }
+
+    #[test]
+    fn test_args_match() {
+        let config = "[ed]
+name = ed
+arg0 = /usr/bin/systemctl
+arg1 = start|stop|restart
+arg2 = [a-z]+\\.service
+args_max = 2
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        args_cmd(&mut ro, &["/usr/bin/systemctl", "restart", "nginx.service"]);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        args_cmd(&mut ro, &["/usr/bin/systemctl", "enable", "nginx.service"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        // too few arguments
+        args_cmd(&mut ro, &["/usr/bin/systemctl", "restart"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        // too many arguments
+        args_cmd(
+            &mut ro,
+            &[
+                "/usr/bin/systemctl",
+                "restart",
+                "nginx.service",
+                "sshd.service",
+            ],
+        );
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        // a space within an argument does not need escaping, nor can it extend a match
+        args_cmd(&mut ro, &["/usr/bin/systemctl", "restart nginx.service"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_args_rest() {
+        let config = "[ed]
+name = ed
+arg0 = /bin/echo
+args_rest = [^\\\\]*
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        args_cmd(&mut ro, &["/bin/echo", "hello world", "again"]);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        args_cmd(&mut ro, &["/bin/echo"]);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        args_cmd(&mut ro, &["/bin/echo", "hello", "back\\slash"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_args_with_rule() {
+        // the rule and the arguments must both match
+        let config = "[ed]
+name = ed
+rule = /bin/echo .*
+args_rest = [a-z]+
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        args_cmd(&mut ro, &["/bin/echo", "hello", "world"]);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        args_cmd(&mut ro, &["/bin/echo", "hello", "World"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        args_cmd(&mut ro, &["/bin/cat", "hello"]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_args_errors() {
+        let config = "[ed]
+name = ed
+arg1 = (
+args_max = two
+args_rest = [
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        let kinds: Vec<ConfigErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                ConfigErrorKind::BadRegex,
+                ConfigErrorKind::BadValue,
+                ConfigErrorKind::BadRegex
+            ]
+        );
+        assert_eq!(errors[0].line, Some(3));
+
+        let config = "[ed]
+name = ed
+argv = .*
+"
+        .to_string();
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn test_args_list() {
+        let config = "[ed]
+name = ed
+arg0 = /usr/bin/systemctl
+arg1 = status
+args_max = 2
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "ed");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let list = produce_list(&vec_eo, &ro);
+
+        assert_eq!(
+            list,
+            [
+                "  in file: static",
+                "    ed:root (pass=true,dirs=): .* args(arg0=/usr/bin/systemctl arg1=status args_max=2)"
+            ]
+        );
+    }
+
+    /// the cache is only trusted when root owned, so these need to run as root
+    fn cache_test_dir(name: &str) -> Option<PathBuf> {
+        if !nix::unistd::getuid().is_root() {
+            return None;
+        }
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("please.d")).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Some(dir)
+    }
+
+    fn write_cache_config(dir: &Path) -> String {
+        let config = dir.join("please.ini");
+        std::fs::write(
+            &config,
+            format!(
+                "[ed_ls]\nname = ed\nrule = /bin/ls\n\n[inc]\nincludedir = {}\n",
+                dir.join("please.d").display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("please.d").join("cat.ini"),
+            "[ed_cat]\nname = ed\nrule = /bin/cat\n",
+        )
+        .unwrap();
+        config.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_cache_written_and_loaded() {
+        let dir = match cache_test_dir("cache_load") {
+            Some(dir) => dir,
+            None => return,
+        };
+        let config = write_cache_config(&dir);
+        let cache = dir.join("cache").to_str().unwrap().to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/cat");
+
+        assert!(load_policy_cache(&cache, &config, &ro).is_none());
+        let vec_eo = parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+        assert_eq!(vec_eo.len(), 2);
+
+        let mode = std::fs::metadata(cache_path(&cache, &config))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let vec_eo = load_policy_cache(&cache, &config, &ro).unwrap();
+        assert_eq!(vec_eo.len(), 2);
+        assert_eq!(vec_eo[1].section, "ed_cat");
+        assert_eq!(vec_eo[1].line, Some(1));
+        assert!(vec_eo[1].regex_cache.is_some());
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_invalidated() {
+        let dir = match cache_test_dir("cache_invalid") {
+            Some(dir) => dir,
+            None => return,
+        };
+        let config = write_cache_config(&dir);
+        let cache = dir.join("cache").to_str().unwrap().to_string();
+        let ro = basic_ro("ed", "root");
+
+        parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+        assert!(load_policy_cache(&cache, &config, &ro).is_some());
+
+        // an include changing
+        std::fs::write(
+            dir.join("please.d").join("cat.ini"),
+            "[ed_cat]\nname = ed\nrule = /bin/(cat|tac)\n",
+        )
+        .unwrap();
+        assert!(load_policy_cache(&cache, &config, &ro).is_none());
+
+        let vec_eo = parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+        assert_eq!(vec_eo[1].rule, Some("/bin/(cat|tac)".to_string()));
+        assert!(load_policy_cache(&cache, &config, &ro).is_some());
+
+        // a new file in an includedir
+        std::fs::write(
+            dir.join("please.d").join("ls.ini"),
+            "[ed_ls_l]\nname = ed\nrule = /bin/ls -l\n",
+        )
+        .unwrap();
+        assert!(load_policy_cache(&cache, &config, &ro).is_none());
+        let vec_eo = parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+        assert_eq!(vec_eo.len(), 3);
+
+        // a permission change alone
+        parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+        std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o640)).unwrap();
+        assert!(load_policy_cache(&cache, &config, &ro).is_none());
+        parse_ini_config_file_cached(&config, &ro, &cache).unwrap();
+
+        // a cache that others could have written
+        std::fs::set_permissions(
+            cache_path(&cache, &config),
+            std::fs::Permissions::from_mode(0o622),
+        )
+        .unwrap();
+        assert!(load_policy_cache(&cache, &config, &ro).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_not_written_on_error() {
+        let dir = match cache_test_dir("cache_error") {
+            Some(dir) => dir,
+            None => return,
+        };
+        let config = dir.join("please.ini");
+        std::fs::write(&config, "[ed]\nname = ed\nfoo = bar\n").unwrap();
+        let config = config.to_str().unwrap().to_string();
+        let cache = dir.join("cache").to_str().unwrap().to_string();
+        let ro = basic_ro("ed", "root");
+
+        assert!(parse_ini_config_file_cached(&config, &ro, &cache).is_err());
+        assert!(!std::path::Path::new(&cache_path(&cache, &config)).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_capabilities_config() {
+        let config = "[default:caps]
+name = .*
+rule = .*
+capabilities = net_bind_service
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+permit = true
+
+[ed_strace]
+name = ed
+rule = /bin/echo .*
+capabilities = CAP_SYS_PTRACE, cap_dac_read_search cap_sys_ptrace
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert_eq!(
+            entry.capabilities,
+            Some(vec!["cap_net_bind_service".to_string()])
+        );
+
+        basic_cmd(&mut ro, "/bin/echo hello");
+        let entry = can(&vec_eo, &mut ro);
+        assert_eq!(
+            entry.capabilities,
+            Some(vec![
+                "cap_sys_ptrace".to_string(),
+                "cap_dac_read_search".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_capabilities_errors() {
+        let config = "[ed]
+name = ed
+rule = .*
+capabilities = cap_net_bind_service, cap_fly
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadValue);
+        assert_eq!(errors[0].line, Some(4));
+
+        assert!(parse_capabilities(" , ").is_err());
+    }
+
+    #[test]
+    fn test_capabilities_list() {
+        let config = "[ed]
+name = ed
+rule = /usr/sbin/nginx
+capabilities = net_bind_service
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "ed");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            produce_list(&vec_eo, &ro),
+            [
+                "  in file: static",
+                "    ed:root caps(cap_net_bind_service) (pass=true,dirs=): /usr/sbin/nginx"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_raise_capabilities() {
+        // only root has capabilities to give
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let capabilities = vec!["cap_net_bind_service".to_string()];
+
+        let code = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                if keep_capabilities(&capabilities).is_err()
+                    || !set_privs(
+                        "nobody",
+                        nix::unistd::Uid::from_raw(65534),
+                        nix::unistd::Gid::from_raw(65534),
+                    )
+                    || raise_capabilities(&capabilities).is_err()
+                {
+                    unsafe { libc::_exit(100) };
+                }
+                let status = std::fs::read_to_string("/proc/self/status").unwrap();
+                // cap_net_bind_service is 10
+                let ok = status.contains("CapAmb:\t0000000000000400")
+                    && status.contains("CapBnd:\t0000000000000400");
+                unsafe { libc::_exit(if ok { 0 } else { 101 }) };
+            }
+            ForkResult::Parent { child } => match waitpid(child, None).unwrap() {
+                WaitStatus::Exited(_, code) => code,
+                _ => -1,
+            },
+        };
+
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn test_config_error_unknown_key() {
+        let config = "[ed]
+name = ed
+rule = .*
+colour = blue
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownKey);
+        assert_eq!(errors[0].file, "static");
+        assert_eq!(errors[0].line, Some(4));
+        assert_eq!(errors[0].section, Some("ed".to_string()));
+        assert_eq!(errors[0].key, Some("colour".to_string()));
+        assert_eq!(errors[0].to_string(), "Error parsing static:4");
+    }
+
+    #[test]
+    fn test_config_error_no_section() {
+        let config = "name = ed
+[ed]
+rule = .*
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::NoSection);
+        assert_eq!(errors[0].section, None);
+    }
+
+    #[test]
+    fn test_config_error_bad_regex() {
+        let config = "[ed]
+name = ed
+rule = ^/bin/cat /etc/(
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadRegex);
+        assert_eq!(errors[0].to_string(), "Error parsing static: ed:3");
+    }
+
+    #[test]
+    fn test_config_error_bad_values() {
+        let config = "[ed]
+name = ed
+rule = .*
+notbefore = 20241341
+notafter = 20241301235960
+timeout = soon
+editmode = 999
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        let kinds: Vec<ConfigErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                ConfigErrorKind::BadDate,
+                ConfigErrorKind::BadDate,
+                ConfigErrorKind::BadValue,
+                ConfigErrorKind::BadValue
+            ]
+        );
+
+        // a non-fatal error still leaves the bool readers lenient
+        let mut vec_eo: Vec<EnvOptions> = vec![];
+        let mut bytes = 0;
+        let mut ini_list: HashMap<String, bool> = HashMap::new();
+        assert!(!read_ini_config_str(
+            &config,
+            &mut vec_eo,
+            &ro,
+            false,
+            &mut bytes,
+            &mut ini_list
+        ));
+        assert_eq!(vec_eo.len(), 1);
+    }
+
+    #[test]
+    fn test_config_error_include_relative() {
+        let config = "[inc]
+include = ./some.ini
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Include);
+        assert!(errors[0].fatal);
+    }
+
+    #[test]
+    fn test_config_error_missing_file() {
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file("/nonexistent/please.ini", &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Io);
+        assert_eq!(errors[0].file, "/nonexistent/please.ini");
+        assert_eq!(errors[0].line, None);
+    }
+
+    #[test]
+    fn test_config_error_include_loop() {
+        let dir = std::env::temp_dir().join(format!("please_loop_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.ini");
+        let b = dir.join("b.ini");
+        std::fs::write(
+            &a,
+            format!(
+                "[a]\nname = ed\nrule = .*\n[inc]\ninclude = {}\n",
+                b.display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(&b, format!("[inc]\ninclude = {}\n", a.display())).unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file(a.to_str().unwrap(), &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::IncludeLoop);
+        assert_eq!(errors[0].file, a.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_config_error_include_twice() {
+        let dir = std::env::temp_dir().join(format!("please_twice_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.ini");
+        std::fs::write(&a, "[a]\nname = ed\nrule = .*\n").unwrap();
+
+        let config = format!(
+            "[inc]\ninclude = {}\n[inc_again]\ninclude = {}\n",
+            a.display(),
+            a.display()
+        );
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::IncludeRepeat);
+        assert_eq!(errors[0].file, a.to_str().unwrap());
+        assert!(!errors[0].fatal);
+    }
+
+    fn owner_test_dir(name: &str, mode: u32) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(mode)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_config_owner_writable_directory() {
+        let dir = owner_test_dir("owner_writable", 0o775);
+        let config = dir.join("please.ini");
+        std::fs::write(&config, "[ed]\nname = ed\nrule = /bin/ls\n").unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file(config.to_str().unwrap(), &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Permission);
+        assert_eq!(
+            errors[0].message,
+            format!(
+                "Refusing to parse {} as {} is writable by group or other",
+                config.display(),
+                dir.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_config_owner_sticky_directory() {
+        let dir = owner_test_dir("owner_sticky", 0o1777);
+        let config = dir.join("please.ini");
+        std::fs::write(&config, "[ed]\nname = ed\nrule = /bin/ls\n").unwrap();
+        std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let parsed = parse_ini_config_file(config.to_str().unwrap(), &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(parsed.is_ok());
+    }
+
+    #[test]
+    fn test_config_owner_includedir_writable() {
+        let dir = owner_test_dir("owner_includedir", 0o755);
+        let include = dir.join("please.d");
+        std::fs::create_dir_all(&include).unwrap();
+        std::fs::set_permissions(&include, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let config = dir.join("please.ini");
+        std::fs::write(
+            &config,
+            format!("[inc]\nincludedir = {}\n", include.display()),
+        )
+        .unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file(config.to_str().unwrap(), &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::Permission);
+        assert_eq!(errors[0].line, Some(2));
+        assert!(errors[0].fatal);
+    }
+
+    #[test]
+    fn test_config_owner_uid() {
+        // chown needs root, other users can only own files themselves
+        if !nix::unistd::getuid().is_root() {
+            return;
+        }
+        let dir = owner_test_dir("owner_uid", 0o755);
+        let include = dir.join("admin.ini");
+        std::fs::write(&include, "[ed]\nname = ed\nrule = /bin/ls\n").unwrap();
+        std::os::unix::fs::chown(&include, Some(1000), None).unwrap();
+
+        let config = dir.join("please.ini");
+        std::fs::write(&config, format!("[inc]\ninclude = {}\n", include.display())).unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file(config.to_str().unwrap(), &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::Permission);
+        assert_eq!(
+            errors[0].message,
+            format!(
+                "Refusing to parse {} as {} is owned by uid 1000",
+                include.display(),
+                include.display()
+            )
+        );
+
+        std::fs::write(
+            &config,
+            format!(
+                "[admins]\nadmin_uid = 1000\n[inc]\ninclude = {}\n",
+                include.display()
+            ),
+        )
+        .unwrap();
+        let parsed = parse_ini_config_file(config.to_str().unwrap(), &ro);
+        assert!(parsed.is_ok());
+
+        // an admin cannot name further admins
+        std::fs::write(
+            &include,
+            "[admins]\nadmin_uid = 1001\n[ed]\nname = ed\nrule = /bin/ls\n",
+        )
+        .unwrap();
+        let errors = parse_ini_config_file(config.to_str().unwrap(), &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::Permission);
+        assert_eq!(errors[0].file, include.to_str().unwrap());
+    }
+
+    // echo -n hello | sha256sum
+    const HELLO: &str = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn write_binary(name: &str, content: &str) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("deploy");
+        std::fs::write(&file, content).unwrap();
+        (dir, file.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_digest_match() {
+        let (dir, binary) = write_binary("digest_match", "hello");
+        let config = format!(
+            "[ed]
+name = ed
+exact_rule = {}
+digest = sha256:0000000000000000000000000000000000000000000000000000000000000000, {}
+",
+            binary,
+            HELLO.to_uppercase().replace("SHA256", "sha256")
+        );
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, &binary);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert_eq!(vec_eo[0].digest.as_ref().unwrap().len(), 2);
+        assert!(can(&vec_eo, &mut ro).permit());
+        assert_eq!(
+            ro.located_digest.get(&binary),
+            Some(&Some(HELLO.to_string()))
+        );
+
+        // the binary is replaced
+        std::fs::write(&binary, "goodbye").unwrap();
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, &binary);
+        ro.trace = Some(vec![]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+        assert_eq!(
+            ro.trace.as_ref().unwrap()[0].failed,
+            vec![MatchCheck::Digest]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_digest_unreadable() {
+        use nix::sys::wait::{waitpid, WaitStatus};
+        use nix::unistd::{fork, ForkResult};
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, binary) = write_binary("digest_unreadable", "hello");
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let opened = OpenBinary::open(&binary).unwrap();
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                // only root can read it, the user is nobody
+                let mut ro = basic_ro("nobody", "root");
+                ro.original_uid = nix::unistd::Uid::from_raw(65534);
+                ro.original_gid = nix::unistd::Gid::from_raw(65534);
+                drop_privs(&ro);
+                let ok = std::fs::read(&binary).is_err()
+                    && located_digest(&mut ro, &binary) == Some(HELLO.to_string())
+                    && as_root(&ro, || opened.digest()).ok() == Some(HELLO.to_string())
+                    && nix::unistd::geteuid().as_raw() == 65534;
+                unsafe { libc::_exit(if ok { 0 } else { 1 }) };
+            }
+            ForkResult::Parent { child } => {
+                let status = waitpid(child, None).unwrap();
+                std::fs::remove_dir_all(&dir).unwrap();
+                assert_eq!(status, WaitStatus::Exited(child, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_digest_errors() {
+        let config = "[ed]
+name = ed
+rule = /bin/true
+digest = md5:5d41402abc4b2a76b9719d911017c592
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadValue);
+        assert_eq!(errors[0].line, Some(4));
+
+        assert!(parse_digests("sha256:2cf24dba").is_err());
+        assert!(parse_digests(" , ").is_err());
+        assert_eq!(parse_digests(HELLO).unwrap(), vec![HELLO.to_string()]);
+    }
+
+    #[test]
+    fn test_digest_report() {
+        let (dir, binary) = write_binary("digest_report", "hello");
+        let config = format!(
+            "[pinned]
+name = ed
+rule = ^{} .*$
+digest = {}
+
+[unpinned]
+name = ed
+arg0 = {}
+
+[wild]
+name = ed
+rule = .*
+digest = {}
+",
+            binary, HELLO, binary, HELLO
+        );
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let report = digest_report(&vec_eo);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            report,
+            [
+                format!(
+                    "[pinned] at static:1: {} digest={} (matches)",
+                    binary, HELLO
+                ),
+                format!(
+                    "[unpinned] at static:6: {} digest={} (not pinned)",
+                    binary, HELLO
+                ),
+                "[wild] at static:10: cannot find a program in the rule".to_string(),
+            ]
+        );
+    }
+
+    fn at_hour(y: i32, m: u32, d: u32, h: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, 0, 0)
+            .unwrap()
+    }
+
+    fn write_dates(name: &str, dates: &str) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let file = dir.join("freeze.dates");
+        std::fs::write(&file, dates).unwrap();
+        (dir, file.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_exclude_dates_freeze() {
+        let (dir, file) = write_dates(
+            "exclude_freeze",
+            "# change freeze\n20261220 20270104\n\n20261231 # overlaps\n2027-03-01T09:00Z 2027-03-01T17:00Z\n",
+        );
+        let config = format!(
+            "[default:freeze]
+exclude_dates_file = {}
+permit = false
+
+[ed]
+name = ed
+rule = .*
+
+[break_glass]
+name = ed
+rule = /usr/sbin/reboot
+exclude_dates_file = false
+",
+            file
+        );
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        ro.date = at_hour(2026, 12, 19, 23);
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = at_hour(2026, 12, 25, 12);
+        assert!(!can(&vec_eo, &mut ro).permit());
+        ro.date = at_hour(2027, 1, 5, 0);
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = at_hour(2027, 3, 1, 12);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        ro.date = at_hour(2026, 12, 25, 12);
+        basic_cmd(&mut ro, "/usr/sbin/reboot");
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        assert_eq!(vec_eo[0].exclude_dates.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            excluded_until(&vec_eo[0], &at_hour(2026, 12, 25, 12)),
+            NaiveDate::from_ymd_opt(2027, 1, 4)
+                .unwrap()
+                .and_hms_opt(23, 59, 59)
+        );
+    }
+
+    #[test]
+    fn test_exclude_dates_lint() {
+        let (dir, file) = write_dates("exclude_lint", "20261220 20270104\n");
+        let config = format!(
+            "[ed_unfrozen]
+name = ed
+rule = /bin/.*
+exclude_dates_file = {}
+last = true
+
+[ed_ls]
+name = ed
+rule = /bin/ls
+",
+            file
+        );
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!lint_config(&vec_eo, &ro)
+            .iter()
+            .any(|f| f.message.starts_with("can never match")));
+    }
+
+    #[test]
+    fn test_exclude_dates_list() {
+        let (dir, file) = write_dates("exclude_list", "20261220 20270104\n");
+        let config = format!(
+            "[ed]
+name = ed
+rule = /bin/bash
+exclude_dates_file = {}
+",
+            file
+        );
+
+        let mut ro = basic_ro("ed", "ed");
+        ro.date = at_hour(2026, 12, 25, 12);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let list = produce_list(&vec_eo, &ro);
+
+        assert_eq!(
+            list,
+            [
+                "  in file: static",
+                "    ed: frozen(until 2027-01-04 23:59:59) as root (pass=true,dirs=): /bin/bash"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude_dates_errors() {
+        let (dir, file) = write_dates("exclude_errors", "20261220\n20270104 20261220\n");
+        let config = format!(
+            "[ed]
+name = ed
+rule = .*
+exclude_dates_file = {}
+",
+            file
+        );
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadDate);
+        assert_eq!(errors[0].file, file);
+        assert_eq!(errors[0].line, Some(2));
+
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o666)).unwrap();
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(errors[0].kind, ConfigErrorKind::Permission);
+        assert_eq!(errors[0].line, Some(4));
+
+        let config = "[ed]
+name = ed
+rule = .*
+exclude_dates_file = freeze.dates
+"
+        .to_string();
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadValue);
+    }
+
+    #[test]
+    fn test_trace_records_first_failure() {
+        let config = "[ed_ls]
+name = ed
+target = root
+rule = /bin/ls
+
+[ed_cat]
+name = ed
+target = postgres
+rule = /bin/cat
+
+[bob_cat]
+name = bob
+target = root
+rule = /bin/cat
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/cat");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        ro.trace = Some(vec![]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let trace = ro.trace.as_ref().unwrap();
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0].failed, vec![MatchCheck::Rule]);
+        assert_eq!(trace[1].failed, vec![MatchCheck::Target]);
+        assert_eq!(trace[2].failed, vec![MatchCheck::Name]);
+        assert_eq!(
+            trace[1].to_string(),
+            "[ed_cat] at static:6: target mismatch"
+        );
+    }
+
+    #[test]
+    fn test_trace_off_by_default() {
+        let config = "[ed_ls]
+name = ed
+rule = /bin/ls
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/ls");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+        assert!(ro.trace.is_none());
+    }
+
+    #[test]
+    fn test_explain_needs_list() {
+        let config = "[ed_ls]
+name = ed
+rule = /bin/ls
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/cat");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        match explain(&vec_eo, &ro) {
+            Explanation::NotListable => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_explain_closest() {
+        let config = "[ed_list]
+name = ed
+type = list
+target = ed
+
+[bob_cat]
+name = bob
+target = postgres
+rule = /bin/cat .*
+
+[ed_cat_var]
+name = ed
+target = root
+dir = /tmp
+rule = /bin/cat /var/.*
+
+[ed_cat_etc]
+name = ed
+target = root
+rule = /bin/cat /etc/.*
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "postgres");
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        match explain(&vec_eo, &ro) {
+            Explanation::Closest(trace) => {
+                assert_eq!(trace.section, "ed_cat_etc");
+                assert_eq!(trace.failed, vec![MatchCheck::Target, MatchCheck::Rule]);
+            }
+            x => panic!("unexpected {:?}", x),
+        }
+
+        ro.target = "root".to_string();
+        match explain(&vec_eo, &ro) {
+            Explanation::Closest(trace) => {
+                assert_eq!(trace.section, "ed_cat_etc");
+                assert_eq!(trace.failed, vec![MatchCheck::Rule]);
+                assert_eq!(
+                    Explanation::Closest(trace).to_string(),
+                    "Closest section [ed_cat_etc] at static:17: rule mismatch"
+                );
+            }
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_explain_permit_false() {
+        let config = "[ed_list]
+name = ed
+type = list
+target = ed
+
+[ed_cat]
+name = ed
+rule = /bin/cat .*
+
+[ed_no_shadow]
+name = ed
+rule = /bin/cat /etc/shadow
+permit = false
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/cat /etc/shadow");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let explanation = explain(&vec_eo, &ro);
+        assert_eq!(
+            explanation.to_string(),
+            "Closest section [ed_no_shadow] at static:10: matched, permit=false"
+        );
+    }
+
+    #[test]
+    fn test_explain_hides_other_users() {
+        let config = "[ed_list]
+name = ed
+type = list
+target = ed
+
+[bob_cat]
+name = bob
+rule = /bin/cat .*
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/cat /etc/hosts");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        match explain(&vec_eo, &ro) {
+            Explanation::NoSection => {}
+            x => panic!("unexpected {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_explain_link() {
+        let dir = std::env::temp_dir()
+            .canonicalize()
+            .unwrap()
+            .join(format!("please_explain_link_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("real"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+        let link = dir.join("link").to_str().unwrap().to_string();
+        let real = dir.join("real").to_str().unwrap().to_string();
+
+        let config = format!(
+            "[ed_list]
+name = ed
+type = list
+target = ed
+
+[ed_link]
+name = ed
+rule = ^{}$
+resolve_links = true
+",
+            link
+        );
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, &link);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let permit = can(&vec_eo, &mut ro).permit();
+        let explanation = explain(&vec_eo, &ro).to_string();
+        let listed = produce_list(&vec_eo, &basic_ro("ed", "ed"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!permit);
+        assert_eq!(
+            explanation,
+            format!(
+                "Closest section [ed_link] at static:6: rule mismatch, {} links to {}, which rules match",
+                link, real
+            )
+        );
+        assert_eq!(
+            listed[1],
+            format!(
+                "    ed_link:root (pass=true,dirs=): ^{}$ ({} links to {}, which rules match)",
+                link, link, real
+            )
+        );
+    }
+
+    #[test]
+    fn test_inherit_base() {
+        let config = "[base]
+target = postgres
+require_pass = false
+
+[ed_psql]
+inherit = base
+name = ed
+rule = /usr/bin/psql
+"
+        .to_string();
+
+        let mut bytes = 0;
+        let mut ini_list: HashMap<String, bool> = HashMap::new();
+        let mut vec_eo: Vec<EnvOptions> = vec![];
+        let mut ro = basic_ro("ed", "postgres");
+        basic_cmd(&mut ro, "/usr/bin/psql");
+
+        read_ini_config_str(&config, &mut vec_eo, &ro, false, &mut bytes, &mut ini_list);
+
+        // base has no name so is never a match by itself
+        assert_eq!(vec_eo.len(), 1);
+        assert_eq!(vec_eo[0].inherit, Some("base".to_string()));
+
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert!(!entry.require_pass());
+        assert_eq!(entry.section, "ed_psql");
+    }
+
+    #[test]
+    fn test_inherit_own_keys_win() {
+        let config = "[base]
+name = ed
+target = postgres
+rule = /usr/bin/psql
+require_pass = false
+
+[ben_psql]
+name = ben
+require_pass = true
+inherit = base
+"
+        .to_string();
+
+        let mut bytes = 0;
+        let mut ini_list: HashMap<String, bool> = HashMap::new();
+        let mut vec_eo: Vec<EnvOptions> = vec![];
+        let mut ro = basic_ro("ben", "postgres");
+        basic_cmd(&mut ro, "/usr/bin/psql");
+
+        read_ini_config_str(&config, &mut vec_eo, &ro, false, &mut bytes, &mut ini_list);
+
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert!(entry.require_pass());
+        assert_eq!(entry.section, "ben_psql");
+
+        let mut ro = basic_ro("ed", "postgres");
+        basic_cmd(&mut ro, "/usr/bin/psql");
+        let entry = can(&vec_eo, &mut ro);
+        assert_eq!(entry.section, "base");
+    }
+
+    #[test]
+    fn test_inherit_env_assign_override() {
+        let config = "[base]
+name = ed
+target = postgres
+rule = /usr/bin/psql
+env_assign.PGHOST = parent
+env_assign.PGPORT = 5432
+
+[early]
+name = ben
+env_assign.PGHOST = early
+inherit = base
+
+[late]
+name = carl
+inherit = base
+env_assign.PGHOST = late
+"
+        .to_string();
+
+        let mut bytes = 0;
+        let mut ini_list: HashMap<String, bool> = HashMap::new();
+        let mut vec_eo: Vec<EnvOptions> = vec![];
+        let ro = basic_ro("ed", "postgres");
+
+        read_ini_config_str(&config, &mut vec_eo, &ro, false, &mut bytes, &mut ini_list);
+
+        for (user, host) in [("ben", "early"), ("carl", "late")] {
+            let mut ro = basic_ro(user, "postgres");
+            basic_cmd(&mut ro, "/usr/bin/psql");
+            let entry = can(&vec_eo, &mut ro);
+            let env_assign = entry.env_assign.unwrap();
+            assert_eq!(env_assign.get("PGHOST"), Some(&host.to_string()));
+            assert_eq!(env_assign.get("PGPORT"), Some(&"5432".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_inherit_chain() {
+        let config = "[base]
+target = postgres
+
+[psql]
+inherit = base
+rule = /usr/bin/psql
+
+[ed_psql]
+inherit = psql
+name = ed
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "postgres");
+        basic_cmd(&mut ro, "/usr/bin/psql");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.target = "root".to_string();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_inherit_missing_parent() {
+        let config = "[ed_psql]
+name = ed
+inherit = base
+
+[base]
+target = postgres
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "postgres");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Inherit);
+        assert_eq!(errors[0].file, "static");
+        assert_eq!(errors[0].line, Some(3));
+        assert_eq!(errors[0].section, Some("ed_psql".to_string()));
+    }
+
+    #[test]
+    fn test_inherit_self() {
+        let config = "[ed_psql]
+name = ed
+inherit = ed_psql
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "postgres");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::InheritLoop);
+        assert_eq!(errors[0].line, Some(3));
+    }
+
+    #[test]
+    fn test_inherit_from_include() {
+        let dir = std::env::temp_dir().join(format!("please_inherit_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("base.ini");
+        std::fs::write(&base, "[base]\ntarget = postgres\nrequire_pass = false\n").unwrap();
+
+        let config = format!(
+            "[inc]\ninclude = {}\n[ed_psql]\ninherit = base\nname = ed\nrule = /usr/bin/psql\n",
+            base.display()
+        );
+
+        let mut ro = basic_ro("ed", "postgres");
+        basic_cmd(&mut ro, "/usr/bin/psql");
+        let vec_eo = parse_ini_config_str(&config, &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let vec_eo = vec_eo.unwrap();
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert!(!entry.require_pass());
+        assert_eq!(entry.file_name, "static");
+    }
+
+    #[test]
+    fn test_isolate_merge() {
+        let config = "[default:isolate]
+name = .*
+rule = .*
+private_tmp = true
+hide_paths = /home, /root
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+readonly_paths = /etc /usr
+permit = true
+
+[ed_jail]
+name = ed
+rule = /bin/echo .*
+chroot = /srv/jail
+private_tmp = false
+hide_paths = /srv/jail/private
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/lib");
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert!(entry.isolated());
+        assert_eq!(entry.chroot, None);
+        assert_eq!(entry.private_tmp, Some(true));
+        assert_eq!(
+            entry.readonly_paths,
+            Some(vec!["/etc".to_string(), "/usr".to_string()])
+        );
+        assert_eq!(
+            entry.hide_paths,
+            Some(vec!["/home".to_string(), "/root".to_string()])
+        );
+
+        basic_cmd(&mut ro, "/bin/echo today");
+        let entry = can(&vec_eo, &mut ro);
+        assert_eq!(entry.chroot, Some("/srv/jail".to_string()));
+        assert_eq!(entry.private_tmp, Some(false));
+        assert_eq!(entry.readonly_paths, None);
+        assert_eq!(
+            entry.hide_paths,
+            Some(vec!["/srv/jail/private".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_isolate_errors() {
+        let config = "[ed]
+name = ed
+rule = .*
+chroot = srv/jail
+readonly_paths = /etc, usr
+hide_paths = ,
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        let lines: Vec<Option<usize>> = errors.iter().map(|e| e.line).collect();
+
+        assert!(errors.iter().all(|e| e.kind == ConfigErrorKind::BadValue));
+        assert_eq!(lines, vec![Some(4), Some(5), Some(6)]);
+    }
+
+    #[test]
+    fn test_isolate_list() {
+        let config = "[ed]
+name = ed
+rule = /usr/bin/report
+chroot = /srv/jail
+private_tmp = true
+readonly_paths = /etc /usr
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "ed");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            produce_list(&vec_eo, &ro),
+            [
+                "  in file: static",
+                "    ed: isolate(chroot=/srv/jail private_tmp readonly_paths=/etc,/usr) as root (pass=true,dirs=): /usr/bin/report"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_isolate() {
+        // mounts need root
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+
+        let dir = std::env::temp_dir().join(format!("please_isolate_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("ro")).unwrap();
+        std::fs::create_dir_all(dir.join("hidden")).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("hidden/secret"), "secret").unwrap();
+
+        let config = format!(
+            "[ed]
+name = ed
+rule = .*
+readonly_paths = {}/ro
+hide_paths = {}/hidden
+",
+            dir.display(),
+            dir.display()
+        );
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        let code = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                if isolate(&vec_eo[0], None).is_err() {
+                    unsafe { libc::_exit(100) };
+                }
+                let read_only = std::fs::write(dir.join("ro/file"), "x").is_err();
+                let hidden = !dir.join("hidden/secret").exists();
+                unsafe { libc::_exit(if read_only && hidden { 0 } else { 101 }) };
+            }
+            ForkResult::Parent { child } => match waitpid(child, None).unwrap() {
+                WaitStatus::Exited(_, code) => code,
+                _ => -1,
+            },
+        };
+
+        // outside the child nothing changed
+        let secret = std::fs::read_to_string(dir.join("hidden/secret")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(code, 0);
+        assert_eq!(secret, "secret");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_isolate_submounts() {
+        // mounts need root
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+
+        let dir = std::env::temp_dir().join(format!("please_submount_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("ro/sub")).unwrap();
+
+        let config = format!(
+            "[ed]
+name = ed
+rule = .*
+readonly_paths = {}/ro
+",
+            dir.display()
+        );
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        let code = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                // the tmpfs is only seen by this child
+                let sub = std::ffi::CString::new(dir.join("ro/sub").to_str().unwrap()).unwrap();
+                let tmpfs = std::ffi::CString::new("tmpfs").unwrap();
+                let root = std::ffi::CString::new("/").unwrap();
+                let mounted = unsafe {
+                    libc::unshare(libc::CLONE_NEWNS) == 0
+                        && libc::mount(
+                            std::ptr::null(),
+                            root.as_ptr(),
+                            std::ptr::null(),
+                            libc::MS_REC | libc::MS_PRIVATE,
+                            std::ptr::null(),
+                        ) == 0
+                        && libc::mount(
+                            tmpfs.as_ptr(),
+                            sub.as_ptr(),
+                            tmpfs.as_ptr(),
+                            libc::MS_NOSUID,
+                            std::ptr::null(),
+                        ) == 0
+                };
+                if !mounted || isolate(&vec_eo[0], None).is_err() {
+                    unsafe { libc::_exit(100) };
+                }
+                let read_only = std::fs::write(dir.join("ro/sub/file"), "x").is_err();
+                unsafe { libc::_exit(if read_only { 0 } else { 101 }) };
+            }
+            ForkResult::Parent { child } => match waitpid(child, None).unwrap() {
+                WaitStatus::Exited(_, code) => code,
+                _ => -1,
+            },
+        };
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn test_limits_merge() {
+        let config = "[default:limits]
+name = .*
+rule = .*
+rlimit.nofile = 1024
+rlimit.cpu = 3600
+nice = 10
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+rlimit.as = 2G
+rlimit.cpu = unlimited
+ioprio = best-effort:7
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+
+        let rlimit = entry.rlimit.unwrap();
+        assert_eq!(rlimit.get("nofile"), Some(&1024));
+        assert_eq!(rlimit.get("as"), Some(&(2 << 30)));
+        assert_eq!(rlimit.get("cpu"), Some(&RLIM_UNLIMITED));
+        assert_eq!(entry.nice, Some(10));
+        assert_eq!(entry.ioprio, Some("best-effort:7".to_string()));
+    }
+
+    #[test]
+    fn test_limits_errors() {
+        let config = "[ed]
+name = ed
+rule = .*
+rlimit.stack = 1024
+rlimit.nofile = 1G
+nice = 20
+ioprio = idle:3
+ioprio = sometimes
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        let lines: Vec<Option<usize>> = errors.iter().map(|e| e.line).collect();
+
+        assert!(errors.iter().all(|e| e.kind == ConfigErrorKind::BadValue));
+        assert_eq!(lines, vec![Some(4), Some(5), Some(6), Some(7), Some(8)]);
+
+        assert_eq!(parse_ioprio("realtime:0"), Ok(1 << 13));
+        assert_eq!(parse_ioprio("best-effort"), Ok(2 << 13 | 4));
+        assert_eq!(parse_ioprio("idle"), Ok(3 << 13));
+        assert_eq!(parse_rlimit("as", "512m"), Ok(512 << 20));
+    }
+
+    #[test]
+    fn test_limits_list() {
+        let config = "[ed]
+name = ed
+rule = /usr/bin/updatedb
+rlimit.nofile = 64
+nice = 19
+ioprio = idle
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "ed");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            produce_list(&vec_eo, &ro),
+            [
+                "  in file: static",
+                "    ed: limits(rlimit.nofile=64 nice=19 ioprio=idle) as root (pass=true,dirs=): /usr/bin/updatedb"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let config = "[ed]
+name = ed
+rule = .*
+rlimit.nofile = 64
+nice = 5
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                if set_limits(&vec_eo[0]).is_err() {
+                    unsafe { libc::_exit(100) };
+                }
+                let mut rlim = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlim) };
+                let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+                let ok = rlim.rlim_cur == 64 && rlim.rlim_max == 64 && nice == 5;
+                unsafe { libc::_exit(if ok { 0 } else { 101 }) };
+            }
+            ForkResult::Parent { child } => {
+                assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+            }
+        }
+    }
+
+    fn lint(config: &str, name: &str) -> Vec<LintFinding> {
+        let ro = basic_ro(name, "root");
+        let vec_eo = parse_ini_config_str(config, &ro).unwrap();
+        lint_config(&vec_eo, &ro)
+    }
+
+    #[test]
+    fn test_lint_clean() {
+        let config = "[ed_ls]
+name = ed
+target = root
+rule = /bin/ls( .*)?
+";
+        assert_eq!(lint(config, "ed").len(), 0);
+    }
+
+    #[test]
+    fn test_lint_last_shadows() {
+        let config = "[ed_all]
+name = ed
+target = postgres
+rule = /bin/.*
+last = true
+
+[ed_ls]
+name = ed
+target = postgres
+rule = /bin/ls
+
+[ed_ls_root]
+name = ed
+target = root
+rule = /bin/ls
+";
+        let findings = lint(config, "ed");
+        let shadowed: Vec<&LintFinding> = findings
+            .iter()
+            .filter(|f| f.message.starts_with("can never match"))
+            .collect();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].section, "ed_ls");
+        assert_eq!(shadowed[0].line, Some(7));
+        assert_eq!(shadowed[0].severity, LintSeverity::Warning);
+        assert_eq!(
+            shadowed[0].to_string(),
+            "static:7: warning: [ed_ls] can never match, [ed_all] at static:1 has last=true and matches first"
+        );
+    }
+
+    #[test]
+    fn test_lint_last_schedule() {
+        let config = "[ed_office]
+name = ed
+rule = /bin/.*
+schedule = Mon-Fri 08:00-18:00
+last = true
+
+[ed_ls]
+name = ed
+rule = /bin/ls
+";
+        let findings = lint(config, "ed");
+        assert!(!findings
+            .iter()
+            .any(|f| f.message.starts_with("can never match")));
+    }
+
+    #[test]
+    fn test_lint_last_digest() {
+        let config = "[ed_pinned]
+name = ed
+rule = /bin/ls
+digest = sha256:2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae
+last = true
+
+[ed_ls]
+name = ed
+rule = /bin/ls
+";
+        let findings = lint(config, "ed");
+        assert!(!findings
+            .iter()
+            .any(|f| f.message.starts_with("can never match")));
+    }
+
+    #[test]
+    fn test_lint_last_unmodelled_keys() {
+        for key in ["search_path = /opt/bin", "resolve_links = true"] {
+            let config = format!(
+                "[ed_all]
+name = ed
+rule = /bin/.*
+{}
+last = true
+
+[ed_ls]
+name = ed
+rule = /bin/ls
+",
+                key
+            );
+            let findings = lint(&config, "ed");
+            assert!(
+                !findings
+                    .iter()
+                    .any(|f| f.message.starts_with("can never match")),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_lint_permit_false_overridden() {
+        let config = "[ed_no_cat]
+name = ed
+rule = /bin/cat
+permit = false
+
+[ed_bin]
+name = ed
+rule = /bin/.*
+
+[ed_no_cat_later]
+name = ed
+rule = /bin/cat
+permit = false
+";
+        let findings = lint(config, "ed");
+        let overridden: Vec<&LintFinding> = findings
+            .iter()
+            .filter(|f| f.message.starts_with("permit=false is overridden"))
+            .collect();
+        assert_eq!(overridden.len(), 1);
+        assert_eq!(overridden[0].section, "ed_no_cat");
+        assert_eq!(overridden[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_lint_root_wildcard() {
+        let config = "[ed_all]
+name = ed
+target = root
+rule = .*
+
+[ed_nopass]
+name = ed
+target = root
+rule = ^.*$
+require_pass = false
+
+[ed_postgres]
+name = ed
+target = postgres
+rule = .*
+";
+        let findings = lint(config, "ed");
+        let wildcards: Vec<&LintFinding> = findings
+            .iter()
+            .filter(|f| f.message.contains("grants root"))
+            .collect();
+        assert_eq!(wildcards.len(), 2);
+        assert_eq!(wildcards[0].severity, LintSeverity::Warning);
+        assert_eq!(wildcards[1].severity, LintSeverity::Error);
+        assert_eq!(wildcards[1].section, "ed_nopass");
+    }
+
+    #[test]
+    fn test_lint_shell_escape() {
+        let config = "[ed_less]
+name = ed
+rule = /usr/bin/less /var/log/.*
+
+[ed_find]
+name = ed
+exact_rule = /usr/bin/find /tmp -name core
+
+[ed_vim]
+name = ed
+rule = /usr/bin/vim?
+
+[ed_no_shell]
+name = ed
+rule = /usr/bin/bash
+permit = false
+";
+        let findings = lint(config, "ed");
+        assert_eq!(findings.len(), 3);
+        assert!(findings[0].message.starts_with("less "));
+        assert!(findings[1].message.starts_with("find "));
+        assert!(findings[2].message.starts_with("vim "));
+    }
+
+    #[test]
+    fn test_lint_editmode_and_exitcmd() {
+        let config = "[ed_edit]
+name = ed
+type = edit
+rule = /etc/motd
+editmode = 0646
+exitcmd = /bin/true
+";
+        let findings = lint(config, "ed");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, LintSeverity::Error);
+        assert!(findings[0].message.contains("world writable"));
+
+        let config = "[ed_edit]
+name = ed
+type = edit
+rule = /etc/motd
+editmode = 600
+exitcmd = /nonexistent/check
+";
+        let findings = lint(config, "ed");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, LintSeverity::Warning);
+        assert!(findings[0].message.contains("/nonexistent/check"));
+    }
+
+    #[cfg(target_os = "linux")]
+    /// run binary with args and noexec in a child and return its exit code
+    fn noexec_run(binary: &OpenBinary, args: Vec<String>, vars: &[(&str, String)]) -> i32 {
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                for (key, value) in vars {
+                    std::env::set_var(key, value);
+                }
+                unsafe {
+                    let null =
+                        libc::open(b"/dev/null\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+                    libc::dup2(null, 1);
+                    // without root the filter may only be installed with no_new_privs
+                    libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
+                }
+                if binary.noexec().is_err() {
+                    unsafe { libc::_exit(100) };
+                }
+                binary.exec(&args);
+                unsafe { libc::_exit(101) };
+            }
+            ForkResult::Parent { child } => match waitpid(child, None).unwrap() {
+                WaitStatus::Exited(_, code) => code,
+                _ => -1,
+            },
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    /// run sh -c script with noexec in a child and return its exit code
+    fn noexec_sh(script: &str) -> i32 {
+        let sh = resolve_binary("/bin/sh").unwrap();
+        let args = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        noexec_run(&OpenBinary::open(&sh).unwrap(), args, &[])
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_noexec_first_exec_only() {
+        // the shell itself runs, but cannot run anything else
+        assert_eq!(noexec_sh("exit 7"), 7);
+        assert_eq!(noexec_sh("/bin/true"), 126);
+    }
+
+    #[cfg(target_os = "linux")]
+    /// execveat fd, which must fail with EACCES, for the exit code
+    fn execveat_errno(fd: i32, path: &[u8]) -> i32 {
+        let argv = [b"sh\0".as_ptr() as *const libc::c_char, std::ptr::null()];
+        let envp: [*const libc::c_char; 1] = [std::ptr::null()];
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_execveat,
+                fd,
+                path.as_ptr(),
+                argv.as_ptr(),
+                envp.as_ptr(),
+                libc::AT_EMPTY_PATH,
+            )
+        };
+        if rc == -1 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EACCES) {
+            0
+        } else {
+            1
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_noexec_execveat() {
+        // run again by noexec_run below, where the probe is what noexec has allowed to run
+        if let Ok(fd) = std::env::var("PLEASE_NOEXEC_PROBE") {
+            let fd: i32 = fd.parse().unwrap();
+            // an absolute path ignores the descriptor
+            let absolute = execveat_errno(fd, b"/bin/sh\0");
+            // another binary in place of the one that was run
+            let other =
+                unsafe { libc::open(b"/bin/sh\0".as_ptr() as *const libc::c_char, libc::O_PATH) };
+            unsafe { libc::dup2(other, fd) };
+            let replaced = execveat_errno(fd, b"\0");
+            unsafe { libc::_exit(10 + absolute * 2 + replaced) };
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        let binary = OpenBinary::open(exe).unwrap();
+        let args = vec![
+            exe.to_string(),
+            "test::test_noexec_execveat".to_string(),
+            "--exact".to_string(),
+            "--test-threads=1".to_string(),
+        ];
+        let vars = [("PLEASE_NOEXEC_PROBE", binary.as_raw_fd().to_string())];
+        assert_eq!(noexec_run(&binary, args, &vars), 10);
+    }
+
+    #[test]
+    fn test_noexec_config() {
+        let config = "[default:noexec]
+name = .*
+rule = .*
+noexec = true
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+permit = true
+
+[ed_vi]
+name = ed
+rule = /usr/bin/vi .*
+noexec = false
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let entry = can(&vec_eo, &mut ro);
+        assert_eq!(entry.noexec, Some(true));
+
+        let findings = lint_config(&vec_eo, &ro);
+        let escapes: Vec<&LintFinding> = findings
+            .iter()
+            .filter(|f| f.message.contains("consider noexec=true"))
+            .collect();
+        assert_eq!(escapes.len(), 1);
+        assert_eq!(escapes[0].section, "ed_vi");
+    }
+
+    fn link_dir(name: &str) -> (PathBuf, String, String) {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let binary = dir.join("tool-1.2");
+        std::fs::write(&binary, "hello").unwrap();
+        let link = dir.join("tool");
+        std::os::unix::fs::symlink(&binary, &link).unwrap();
+        (
+            dir,
+            binary.to_str().unwrap().to_string(),
+            link.to_str().unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_binary() {
+        let (dir, binary, link) = link_dir("resolve_binary");
+
+        assert_eq!(resolve_binary(&link), Some(binary.clone()));
+        assert_eq!(resolve_binary(&binary), Some(binary.clone()));
+
+        std::fs::remove_file(&binary).unwrap();
+        assert_eq!(resolve_binary(&link), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rule_matches_link_target() {
+        let (dir, binary, link) = link_dir("link_target");
+        let config = format!(
+            "[ed]
+name = ed
+exact_rule = {}
+",
+            link
+        );
+
+        // the path the user gave is matched
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, &link);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+        assert_eq!(ro.cloned_args.as_ref().unwrap()[0], link);
+
+        // unless the section asks for where it leads
+        let config = format!("{}resolve_links = true\n", config);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let config = format!(
+            "[ed]
+name = ed
+exact_rule = {}
+resolve_links = true
+",
+            binary
+        );
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let permit = can(&vec_eo, &mut ro).permit();
+        let open = OpenBinary::open_resolved(&link).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(permit);
+        assert_eq!(ro.cloned_args.unwrap()[0], binary);
+        assert_eq!(open.path, link);
+    }
+
+    #[test]
+    fn test_open_binary() {
+        let (dir, binary, link) = link_dir("open_binary");
+
+        // links are resolved before they are opened, never by the open
+        let link_open = OpenBinary::open(&link);
+        let open = OpenBinary::open(&binary).unwrap();
+        let digest = open.digest().unwrap();
+
+        // the descriptor still refers to the file that was checked once the path is replaced
+        std::fs::remove_file(&binary).unwrap();
+        std::fs::write(&binary, "goodbye").unwrap();
+        let replaced_digest = open.digest().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(link_open.is_err());
+        assert_eq!(
+            digest,
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(replaced_digest, digest);
+    }
+
+    #[test]
+    fn test_open_binary_fault() {
+        let open = OpenBinary::open("/bin/sh");
+        // /bin/sh may itself be a link
+        if let Ok(open) = open {
+            assert_eq!(open.fault(), None);
+        }
+
+        let (dir, binary, _) = link_dir("open_binary_fault");
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let fault = OpenBinary::open(&binary).unwrap().fault();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(fault.is_some());
+    }
+
+    #[test]
+    fn test_use_pty_config() {
+        let config = "[default:pty]
+name = .*
+rule = .*
+use_pty = true
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+permit = true
+
+[ed_echo]
+name = ed
+rule = /bin/echo .*
+use_pty = false
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        assert_eq!(can(&vec_eo, &mut ro).use_pty, Some(true));
+
+        basic_cmd(&mut ro, "/bin/echo hello");
+        assert_eq!(can(&vec_eo, &mut ro).use_pty, Some(false));
+    }
+
+    /// the name of the terminal on fd
+    fn tty(fd: i32) -> String {
+        unsafe { CStr::from_ptr(libc::ttyname(fd)) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_fork_pty() {
+        let ro = basic_ro("ed", "root");
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+        assert_eq!(unsafe { libc::grantpt(master) + libc::unlockpt(master) }, 0);
+        let outer = unsafe { CStr::from_ptr(libc::ptsname(master)) }
+            .to_string_lossy()
+            .to_string();
+        // while open here, what the child writes stays to be read once it exits
+        let path = std::ffi::CString::new(outer.as_str()).unwrap();
+        let held = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_NOCTTY) };
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                // the child is on the outer terminal, as if a user ran please
+                unsafe {
+                    libc::setsid();
+                    let slave = libc::open(path.as_ptr(), libc::O_RDWR);
+                    for fd in 0..3 {
+                        libc::dup2(slave, fd);
+                    }
+                }
+                let mut entry = EnvOptions::new();
+                entry.use_pty = Some(true);
+                match pleaser::pty::fork_pty(&ro, &entry, None) {
+                    Ok(None) => {}
+                    Ok(Some(outcome)) => outcome.exit(),
+                    Err(_) => unsafe { libc::_exit(100) },
+                }
+                // only the command returns, on a terminal of its own
+                let code = if tty(0) != outer && tty(1) != outer {
+                    // println would be captured by the test harness
+                    unsafe { libc::write(1, b"inner\n".as_ptr() as *const libc::c_void, 6) };
+                    7
+                } else {
+                    101
+                };
+                unsafe { libc::_exit(code) };
+            }
+            ForkResult::Parent { child } => {
+                let status = waitpid(child, None).unwrap();
+                // the output may still be on its way
+                let mut fds = [libc::pollfd {
+                    fd: master,
+                    events: libc::POLLIN,
+                    revents: 0,
+                }];
+                unsafe { libc::poll(fds.as_mut_ptr(), 1, 1000) };
+                unsafe { libc::fcntl(master, libc::F_SETFL, libc::O_NONBLOCK) };
+                let mut buf = [0u8; 256];
+                let read =
+                    unsafe { libc::read(master, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                let output = String::from_utf8_lossy(&buf[..read.max(0) as usize]).to_string();
+                unsafe { libc::close(held) };
+
+                assert_eq!(status, WaitStatus::Exited(child, 7));
+                assert!(output.contains("inner"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_config() {
+        let config = "[default:record]
+name = .*
+rule = .*
+record = true
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+permit = true
+
+[ed_echo]
+name = ed
+rule = /bin/echo .*
+record = false
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        assert_eq!(can(&vec_eo, &mut ro).record, Some(true));
+
+        basic_cmd(&mut ro, "/bin/echo hello");
+        assert_eq!(can(&vec_eo, &mut ro).record, Some(false));
+    }
+
+    fn session_base(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("please_{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_record_session() {
+        let base = session_base("record");
+        let mut ro = basic_ro("ed", "root");
+        ro.original_command = vec!["/bin/cat".to_string(), "/etc/hosts".to_string()];
+
+        let mut recording = create_session(&base, &ro).unwrap();
+        recording.record('i', b"typed\n");
+        recording.record('o', b"printed\n");
+        recording.record('e', b"complained\n");
+        recording.finish("1");
+        let id = recording.id.clone();
+
+        let mut other = basic_ro("bob", "postgres");
+        other.original_command = vec!["psql".to_string()];
+        create_session(&base, &other).unwrap();
+
+        let info = read_session_info(&base, &id).unwrap();
+        assert_eq!(info.user, "ed");
+        assert_eq!(info.target, "root");
+        assert_eq!(info.command, "/bin/cat /etc/hosts");
+        assert_eq!(info.exit, "1");
+
+        assert_eq!(list_sessions(&base, None, None, None).unwrap().len(), 2);
+        assert_eq!(
+            list_sessions(&base, Some("ed"), None, None).unwrap(),
+            vec![info.clone()]
+        );
+        assert_eq!(
+            list_sessions(&base, None, Some("postgres"), None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            list_sessions(&base, None, None, Some(&id[..8]))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            list_sessions(&base, None, None, Some("19700101"))
+                .unwrap()
+                .len(),
+            0
+        );
+
+        let events = read_session(&base, &id).unwrap();
+        let streams: Vec<char> = events.iter().map(|e| e.stream).collect();
+        assert_eq!(streams, vec!['i', 'o', 'e']);
+        assert_eq!(events[1].data, b"printed\n");
+
+        let mut out = vec![];
+        replay(
+            &events,
+            1000.0,
+            Some(Duration::from_millis(10)),
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, b"printed\ncomplained\n");
+
+        let mut out = vec![];
+        replay(&events, 1000.0, None, true, &mut out).unwrap();
+        assert_eq!(out, b"typed\nprinted\ncomplained\n");
+
+        assert!(valid_session_id(&base, &id));
+        assert!(!valid_session_id(&base, "../record"));
+        assert!(!valid_session_id(&base, ""));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_record_bad_timing() {
+        let base = session_base("timing");
+        let ro = basic_ro("ed", "root");
+        let recording = create_session(&base, &ro).unwrap();
+        let id = recording.id.clone();
+        drop(recording);
+        let dir = format!("{}/{}", base, id);
+        std::fs::write(format!("{}/data", dir), b"printed\n").unwrap();
+
+        let mut read = vec![];
+        for timing in [
+            "o 0.5 8",
+            "o -1 3",
+            "o NaN 3",
+            "o inf 3",
+            "o 1e300 3",
+            "o 0.5 9",
+            "o 0.5 18446744073709551615",
+        ] {
+            std::fs::write(format!("{}/timing", dir), format!("{}\n", timing)).unwrap();
+            read.push(read_session(&base, &id).is_ok());
+        }
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(read, vec![true, false, false, false, false, false, false]);
+    }
+
+    #[test]
+    fn test_record_relay() {
+        let base = session_base("relay");
+        let ro = basic_ro("ed", "root");
+        let recording = create_session(&base, &ro).unwrap();
+        let id = recording.id.clone();
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                // no terminal, so the command is given pipes to record
+                unsafe {
+                    let null =
+                        libc::open(b"/dev/null\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+                    for fd in 0..3 {
+                        libc::dup2(null, fd);
+                    }
+                }
+                match pleaser::pty::fork_pty(&ro, &EnvOptions::new(), Some(recording)) {
+                    Ok(None) => {}
+                    Ok(Some(outcome)) => outcome.exit(),
+                    Err(_) => unsafe { libc::_exit(100) },
+                }
+                unsafe {
+                    libc::write(1, b"out\n".as_ptr() as *const libc::c_void, 4);
+                    libc::write(2, b"err\n".as_ptr() as *const libc::c_void, 4);
+                    libc::_exit(3);
+                }
+            }
+            ForkResult::Parent { child } => {
+                let status = waitpid(child, None).unwrap();
+                let events = read_session(&base, &id).unwrap();
+                let info = read_session_info(&base, &id).unwrap();
+                std::fs::remove_dir_all(&base).unwrap();
+
+                assert_eq!(status, WaitStatus::Exited(child, 3));
+                assert_eq!(info.exit, "3");
+                let out: Vec<u8> = events
+                    .iter()
+                    .filter(|e| e.stream == 'o')
+                    .flat_map(|e| e.data.clone())
+                    .collect();
+                let err: Vec<u8> = events
+                    .iter()
+                    .filter(|e| e.stream == 'e')
+                    .flat_map(|e| e.data.clone())
+                    .collect();
+                assert_eq!(out, b"out\n");
+                assert_eq!(err, b"err\n");
+            }
+        }
+    }
+
+    fn at_minute(y: i32, m: u32, d: u32, h: u32, min: u32) -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule_office_hours() {
+        let config = "[ed]
+name = ed
+rule = .*
+schedule = Mon-Fri 08:00-18:00 Europe/London
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert_eq!(
+            vec_eo[0].schedule_cache,
+            Some(parse_schedule("Mon-Fri 08:00-18:00 Europe/London").unwrap())
+        );
+
+        // monday 19th october 2026, london is an hour ahead of UTC until the 25th
+        ro.date = at_minute(2026, 10, 19, 6, 59);
+        assert!(!can(&vec_eo, &mut ro).permit());
+        ro.date = at_minute(2026, 10, 19, 7, 0);
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = at_minute(2026, 10, 19, 16, 59);
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = at_minute(2026, 10, 19, 17, 0);
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        // after the clocks change london is on UTC
+        ro.date = at_minute(2026, 10, 26, 7, 30);
+        assert!(!can(&vec_eo, &mut ro).permit());
+        ro.date = at_minute(2026, 10, 26, 8, 0);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        // saturday
+        ro.date = at_minute(2026, 10, 24, 12, 0);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let schedule = parse_schedule("Fri-Mon 22:00-06:00; Wed 12:00-13:00,17:00-24:00").unwrap();
+        let tz = chrono_tz::Tz::UTC;
+
+        assert_eq!(schedule.windows.len(), 2);
+        // thursday night is not in the window, but friday night carries into saturday
+        assert!(!schedule.contains(&at_minute(2026, 10, 15, 23, 0), &tz));
+        assert!(schedule.contains(&at_minute(2026, 10, 16, 23, 0), &tz));
+        assert!(schedule.contains(&at_minute(2026, 10, 17, 5, 59), &tz));
+        // monday night carries into tuesday, tuesday night does not
+        assert!(schedule.contains(&at_minute(2026, 10, 20, 3, 0), &tz));
+        assert!(!schedule.contains(&at_minute(2026, 10, 21, 3, 0), &tz));
+        assert!(schedule.contains(&at_minute(2026, 10, 21, 12, 30), &tz));
+        assert!(schedule.contains(&at_minute(2026, 10, 21, 23, 59), &tz));
+        assert!(!schedule.contains(&at_minute(2026, 10, 21, 14, 0), &tz));
+    }
+
+    #[test]
+    fn test_schedule_section_timezone() {
+        let config = "[ed]
+name = ed
+rule = .*
+timezone = Asia/Tokyo
+schedule = Sat,Sun
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        // friday 16:00 UTC is saturday 01:00 in tokyo
+        ro.date = at_minute(2026, 10, 16, 16, 0);
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = at_minute(2026, 10, 16, 14, 0);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_schedule_errors() {
+        for bad in [
+            "Mon-Fry 08:00-18:00",
+            "Mon-Fri 8:00-18:00",
+            "Mon-Fri 08:00-25:00",
+            "Mon-Fri 08:00-08:00",
+            "Mon-Fri 08:00-18:00 Europe/Nowhere",
+            "Mon-Fri 08:00-18:00 UTC extra",
+            "Mon;",
+        ] {
+            assert!(parse_schedule(bad).is_err(), "{}", bad);
+        }
+
+        let config = "[ed]
+name = ed
+rule = .*
+schedule = weekdays
+"
+        .to_string();
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadValue);
+        assert_eq!(errors[0].line, Some(4));
+    }
+
+    #[test]
+    fn test_schedule_list() {
+        let config = "[ed]
+name = ed
+rule = /bin/bash
+schedule = Mon-Fri 08:00-18:00 Europe/London
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "ed");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let list = produce_list(&vec_eo, &ro);
+
+        assert_eq!(
+            list,
+            [
+                "  in file: static",
+                "    ed: schedule(Mon-Fri 08:00-18:00 Europe/London) as root (pass=true,dirs=): /bin/bash"
+            ]
+        );
+    }
+
+    const KEYNUM: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn signature_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("keys")).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    fn signify(data: &[u8]) -> String {
+        format!(
+            "untrusted comment: please test\n{}\n",
+            base64::engine::general_purpose::STANDARD.encode(data)
+        )
+    }
+
+    /// write a signify public key to dir/keys and return its signing key
+    fn write_key(dir: &Path, seed: u8) -> SigningKey {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let mut data = b"Ed".to_vec();
+        data.extend_from_slice(&KEYNUM);
+        data.extend_from_slice(key.verifying_key().as_bytes());
+        std::fs::write(dir.join("keys").join("test.pub"), signify(&data)).unwrap();
+        key
+    }
+
+    fn write_signed(file: &Path, content: &str, key: &SigningKey) {
+        std::fs::write(file, content).unwrap();
+        let mut data = b"Ed".to_vec();
+        data.extend_from_slice(&KEYNUM);
+        data.extend_from_slice(&key.sign(content.as_bytes()).to_bytes());
+        std::fs::write(format!("{}.sig", file.display()), signify(&data)).unwrap();
+    }
+
+    fn write_signed_config(dir: &Path, key: &SigningKey) -> (String, PathBuf) {
+        let config = dir.join("please.ini");
+        let include = dir.join("ed.ini");
+        write_signed(
+            &config,
+            &format!(
+                "[signed]\nrequire_signature = {}\n[inc]\ninclude = {}\n",
+                dir.join("keys").display(),
+                include.display()
+            ),
+            key,
+        );
+        write_signed(&include, "[ed]\nname = ed\nrule = /bin/ls\n", key);
+        (config.to_str().unwrap().to_string(), include)
+    }
+
+    #[test]
+    fn test_signature_good() {
+        let dir = signature_test_dir("signature_good");
+        let key = write_key(&dir, 7);
+        let (config, _) = write_signed_config(&dir, &key);
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/ls");
+        let (parsed, signatures) = parse_ini_config_file_signatures(&config, &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let vec_eo = parsed.unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(signatures[0].file, config);
+        assert!(signatures.iter().all(|s| s.state == SignatureState::Good));
+        assert_eq!(signatures[0].keynum, Some(KEYNUM));
+    }
+
+    #[test]
+    fn test_signature_tampered() {
+        let dir = signature_test_dir("signature_tampered");
+        let key = write_key(&dir, 7);
+        let (config, include) = write_signed_config(&dir, &key);
+        std::fs::write(&include, "[ed]\nname = ed\nrule = .*\n").unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let (parsed, signatures) = parse_ini_config_file_signatures(&config, &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let errors = parsed.unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::Signature);
+        assert_eq!(errors[0].file, include.to_str().unwrap());
+        assert!(errors[0].fatal);
+        assert_eq!(signatures[1].state, SignatureState::Bad);
+    }
+
+    #[test]
+    fn test_signature_unsigned_include() {
+        let dir = signature_test_dir("signature_unsigned");
+        let key = write_key(&dir, 7);
+        let (config, include) = write_signed_config(&dir, &key);
+        std::fs::remove_file(format!("{}.sig", include.display())).unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let (parsed, signatures) = parse_ini_config_file_signatures(&config, &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(parsed.is_err());
+        assert_eq!(signatures[1].state, SignatureState::Unsigned);
+    }
+
+    #[test]
+    fn test_signature_include_before_require() {
+        let dir = signature_test_dir("signature_before");
+        let key = write_key(&dir, 7);
+        let include = dir.join("early.ini");
+        std::fs::write(&include, "[early]\nname = ed\nrule = .*\n").unwrap();
+
+        let config = dir.join("please.ini");
+        write_signed(
+            &config,
+            &format!(
+                "[inc]\ninclude = {}\n[signed]\nrequire_signature = {}\n",
+                include.display(),
+                dir.join("keys").display()
+            ),
+            &key,
+        );
+
+        let ro = basic_ro("ed", "root");
+        let (parsed, _) = parse_ini_config_file_signatures(config.to_str().unwrap(), &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let errors = parsed.unwrap_err();
+        let error = errors
+            .iter()
+            .find(|e| e.kind == ConfigErrorKind::Signature)
+            .unwrap();
+        assert!(error.fatal);
+        assert_eq!(error.line, Some(4));
+        assert!(error.message.contains("early.ini"));
+    }
+
+    #[test]
+    fn test_signature_unknown_key() {
+        let dir = signature_test_dir("signature_unknown");
+        let key = write_key(&dir, 7);
+        let (config, _) = write_signed_config(&dir, &key);
+        write_key(&dir, 8);
+
+        let ro = basic_ro("ed", "root");
+        let (parsed, signatures) = parse_ini_config_file_signatures(&config, &ro);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the keynum matches, but the key does not
+        assert!(parsed.is_err());
+        assert_eq!(signatures[0].state, SignatureState::Bad);
+    }
+
+    #[test]
+    fn test_signature_empty_keyring() {
+        let dir = signature_test_dir("signature_empty");
+        let config = dir.join("please.ini");
+        std::fs::write(
+            &config,
+            format!(
+                "[signed]\nrequire_signature = {}\n",
+                dir.join("keys").display()
+            ),
+        )
+        .unwrap();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_file(config.to_str().unwrap(), &ro).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::Signature);
+        assert_eq!(errors[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_simulate_permit() {
+        let config = "[dba]
+name = alice
+hostname = db1
+target = postgres
+rule = /bin/ls( .*)?
+notbefore = 20240101
+"
+        .to_string();
+
+        let mut ro = basic_ro("alice", "postgres");
+        ro.hostname = "db1".to_string();
+        ro.date = parse_date("20240102").unwrap();
+        basic_cmd(&mut ro, "/bin/ls /tmp");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        let simulation = simulate(&vec_eo, &mut ro);
+        assert!(simulation.permit);
+        assert!(simulation.require_pass);
+        assert_eq!(simulation.entry.section, "dba");
+        assert_eq!(
+            simulation.to_string(),
+            "decision: permit\nsection: [dba] at static:1\nrequire_pass: true"
+        );
+
+        ro.date = parse_date("20231231235959").unwrap();
+        let simulation = simulate(&vec_eo, &mut ro);
+        assert!(!simulation.permit);
+        assert_eq!(
+            simulation.to_string(),
+            "decision: deny\nsection: none\nrequire_pass: true"
+        );
+    }
+
+    #[test]
+    fn test_simulate_reason_and_pass() {
+        let config = "[wheel]
+name = wheel
+group = true
+rule = /bin/cat .*
+require_pass = false
+reason = ticket \\d+
+"
+        .to_string();
+
+        let mut ro = basic_ro("bob", "root");
+        let mut groups = HashMap::new();
+        groups.insert(String::from("wheel"), 10);
+        ro.groups = groups;
+        basic_cmd(&mut ro, "/bin/cat /etc/hosts");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        let simulation = simulate(&vec_eo, &mut ro);
+        assert!(!simulation.permit);
+        assert!(!simulation.reason_ok);
+
+        ro.reason = Some("ticket 123".to_string());
+        let simulation = simulate(&vec_eo, &mut ro);
+        assert!(simulation.permit);
+        assert!(simulation.reason_ok);
+        assert!(!simulation.require_pass);
+        assert!(simulation.to_string().contains("reason: ok"));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("20240102").unwrap().to_string(),
+            "2024-01-02 00:00:00"
+        );
+        assert_eq!(
+            parse_date("20240102030405").unwrap().to_string(),
+            "2024-01-02 03:04:05"
+        );
+        assert_eq!(parse_date("2024010"), None);
+        assert_eq!(parse_date("20241302"), None);
+    }
+
+    #[test]
+    fn test_sudoers_user_spec() {
+        let sudoers = "# comment
+alice ALL = (root) /bin/cat /etc/hosts
+bob web1, web2 = (postgres) NOPASSWD: /bin/ls
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+
+        let mut ro = basic_ro("alice", "root");
+        basic_cmd(&mut ro, "/bin/cat /etc/hosts");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert_eq!(vec_eo.len(), 2);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        basic_cmd(&mut ro, "/bin/cat /etc/shadow");
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("bob", "postgres");
+        ro.hostname = "web2".to_string();
+        basic_cmd(&mut ro, "/bin/ls /tmp");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert_eq!(entry.require_pass, Some(false));
+
+        ro.hostname = "db1".to_string();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        ro.hostname = "web2".to_string();
+        ro.target = "root".to_string();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_sudoers_groups_and_aliases() {
+        let sudoers = "User_Alias ADMINS = alice, %wheel
+Cmnd_Alias SERVICES = /bin/systemctl restart *, /bin/systemctl status *
+ADMINS ALL = (ALL) SERVICES, !/bin/systemctl restart sshd
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+
+        let mut ro = basic_ro("carol", "root");
+        let mut groups = HashMap::new();
+        groups.insert(String::from("wheel"), 10);
+        ro.groups = groups;
+        basic_cmd(&mut ro, "/bin/systemctl restart nginx");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        basic_cmd(&mut ro, "/bin/systemctl restart sshd");
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("alice", "postgres");
+        basic_cmd(&mut ro, "/bin/systemctl status nginx");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("dave", "root");
+        basic_cmd(&mut ro, "/bin/systemctl status nginx");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_sudoers_sudoedit() {
+        let sudoers = "alice ALL = sudoedit /etc/hosts".to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+
+        let mut ro = basic_ro("alice", "root");
+        ro.acl_type = Acltype::Edit;
+        basic_cmd(&mut ro, "/etc/hosts");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        ro.acl_type = Acltype::Run;
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_sudoers_defaults() {
+        let sudoers = "Defaults env_reset
+Defaults env_keep += \"LANG LC_ALL\"
+Defaults secure_path = \"/bin:/usr/bin\"
+Defaults timestamp_timeout=5
+Defaults use_pty
+Defaults log_output
+alice ALL = ALL
+bob ALL = NOLOG_OUTPUT: /bin/ls
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+        assert!(!config.contains("# warning"));
+
+        let mut ro = basic_ro("alice", "root");
+        basic_cmd(&mut ro, "ls");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let entry = can(&vec_eo, &mut ro);
+        assert!(entry.permit());
+        assert_eq!(entry.search_path, Some("/bin:/usr/bin".to_string()));
+        assert_eq!(entry.token_timeout, Some(300));
+        assert_eq!(entry.use_pty, Some(true));
+        assert_eq!(entry.record, Some(true));
+
+        let mut bob = basic_ro("bob", "root");
+        basic_cmd(&mut bob, "/bin/ls");
+        assert_eq!(can(&vec_eo, &mut bob).record, Some(false));
+
+        ro.allow_env_list = Some(vec!["LANG".to_string()]);
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        ro.allow_env_list = Some(vec!["PATH".to_string()]);
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_sudoers_runas_group() {
+        let sudoers = "alice ALL = (root:adm) /bin/cat
+bob ALL = (:adm) /bin/cat
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+
+        let mut ro = basic_ro("alice", "root");
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        ro.target_group = Some("adm".to_string());
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("bob", "bob");
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+
+        ro.target_group = Some("adm".to_string());
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        ro.target = "root".to_string();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_sudoers_warnings() {
+        let sudoers = "Defaults lecture=always
+Defaults:alice !requiretty
+alice ALL = NOEXEC: /usr/bin/vi, TIMEOUT=10 /bin/ls
+#bob ALL = ALL
+#1000 ALL = ALL
+#includedir /etc/sudoers.d
+alice ALL = (
+alice ALL = (root
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+        assert!(config.contains("# warning: line 1: Defaults lecture"));
+        assert!(config.contains("# warning: line 2: scoped Defaults"));
+        assert!(!config.contains("# warning: line 3: NOEXEC"));
+        assert!(config.contains("noexec = true"));
+        assert!(config.contains("# warning: line 3: TIMEOUT=10"));
+        assert!(!config.contains("bob"));
+        assert!(config.contains("# warning: line 5: user #1000"));
+        assert!(config.contains("# warning: line 6: includes"));
+        assert!(config.contains("# error: line 7: could not parse: (\n# alice ALL = (\n"));
+        assert!(config.contains("# error: line 8: could not parse: (root\n"));
+        assert!(!config.contains("target = roo\n"));
+
+        let ro = basic_ro("alice", "root");
+        assert_eq!(parse_ini_config_str(&config, &ro).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_sudoers_colon_in_command() {
+        let sudoers = "alice ALL = /usr/bin/ssh host\\:22, /bin/ls
+bob ALL = /bin/ls, /usr/bin/ssh host:22
+Cmnd_Alias SCP = /usr/bin/scp file host\\:/tmp : LS = /bin/ls
+Cmnd_Alias RSYNC = /usr/bin/rsync file host:/tmp
+carl ALL = SCP
+"
+        .to_string();
+
+        let (config, errors) = convert_sudoers(&sudoers, "/etc/sudoers");
+        assert_eq!(
+            errors,
+            vec![
+                "line 2: could not parse: 22, a : within a command is written \\:",
+                "line 4: could not parse alias: /tmp, a : within a command is written \\:",
+            ]
+        );
+        assert!(config.contains("# bob ALL = /bin/ls, /usr/bin/ssh host:22\n"));
+        assert!(config.contains("# Cmnd_Alias RSYNC = /usr/bin/rsync file host:/tmp\n"));
+        assert!(!config.contains("name = bob"));
+        assert!(config.contains("rule = /usr/bin/ssh host:22\n"));
+        assert!(config.contains("rule = /usr/bin/scp file host:/tmp\n"));
+
+        let vec_eo = parse_ini_config_str(&config, &basic_ro("alice", "root")).unwrap();
+        assert_eq!(vec_eo.len(), 3);
+    }
+
+    #[test]
+    fn test_sudoers_round_trip() {
+        let sudoers = "Defaults env_keep = \"TERM\"
+Host_Alias WEB = web1, web2 : DB = db1
+Runas_Alias OP = root, operator
+root ALL=(ALL:ALL) ALL
+%sudo ALL=(ALL:ALL) ALL
+alice WEB = (OP) NOPASSWD: /bin/ls /var/log/*, PASSWD: /usr/bin/less [a-z]*.log, \\
+    sudoedit /etc/hosts /etc/motd : DB = (postgres) /usr/bin/psql \"\"
+bob ALL = /usr/bin/, ^/usr/local/bin/[a-z]+$, NOTBEFORE=20200101000000Z /bin/true
+"
+        .to_string();
+
+        let config = sudoers_to_ini(&sudoers, "/etc/sudoers");
+        let ro = basic_ro("alice", "root");
+        let mut vec_eo: Vec<EnvOptions> = vec![];
+        let mut bytes = 0;
+        let mut ini_list: HashMap<String, bool> = HashMap::new();
+        assert!(!read_ini_config_str(
+            &config,
+            &mut vec_eo,
+            &ro,
+            true,
+            &mut bytes,
+            &mut ini_list
+        ));
+
+        let mut ro = basic_ro("alice", "operator");
+        ro.hostname = "web1".to_string();
+        basic_cmd(&mut ro, "/bin/ls /var/log/syslog");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+
+        let mut ro = basic_ro("bob", "root");
+        basic_cmd(&mut ro, "/usr/bin/id -u");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_supervise_config() {
+        let config = "[default:supervise]
+name = .*
+rule = .*
+supervise = true
+permit = false
+
+[ed]
+name = ed
+rule = /bin/cat .*
+permit = true
+
+[ed_echo]
+name = ed
+rule = /bin/echo .*
+supervise = false
+permit = true
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        basic_cmd(&mut ro, "/bin/cat /var/log/syslog");
+        assert_eq!(can(&vec_eo, &mut ro).supervise, Some(true));
+
+        basic_cmd(&mut ro, "/bin/echo hello");
+        assert_eq!(can(&vec_eo, &mut ro).supervise, Some(false));
+    }
+
+    #[test]
+    fn test_supervise() {
+        // the supervisor gives up root for the invoking user, which is who runs the test
+        let name = uzers::get_current_username().unwrap();
+        let ro = basic_ro(&name.to_string_lossy(), "root");
+        let mut entry = EnvOptions::new();
+        entry.supervise = Some(true);
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => match pleaser::pty::fork_pty(&ro, &entry, None) {
+                Ok(None) => unsafe { libc::_exit(5) },
+                Ok(Some(outcome)) => {
+                    let described = outcome.describe();
+                    let ok = described.starts_with("exit=\"5\" duration=\"")
+                        && described.contains(" maxrss=\"")
+                        && outcome.duration.as_secs() < 10;
+                    unsafe { libc::_exit(if ok { 0 } else { 101 }) };
+                }
+                Err(_) => unsafe { libc::_exit(100) },
+            },
+            ForkResult::Parent { child } => {
+                assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_supervise_signal() {
+        // the supervisor gives up root entirely for nobody, while the command stays root
+        let mut ro = basic_ro("nobody", "root");
+        ro.original_uid = nix::unistd::Uid::from_raw(65534);
+        ro.original_gid = nix::unistd::Gid::from_raw(65534);
+        let mut entry = EnvOptions::new();
+        entry.supervise = Some(true);
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => match pleaser::pty::fork_pty(&ro, &entry, None) {
+                Ok(None) => unsafe {
+                    // rather than wait for ever if TERM is not passed on
+                    libc::alarm(10);
+                    loop {
+                        libc::pause();
+                    }
+                },
+                Ok(Some(outcome)) => {
+                    let ok = outcome.describe().starts_with("signal=\"15\" ");
+                    unsafe { libc::_exit(if ok { 0 } else { 101 }) };
+                }
+                Err(_) => unsafe { libc::_exit(100) },
+            },
+            ForkResult::Parent { child } => {
+                // once the supervisor catches TERM it can be sent to please
+                let status = format!("/proc/{}/status", child);
+                let field = |status: &str, name: &str| {
+                    status
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+                        .unwrap_or_default()
+                };
+                let caught = |status: &str| {
+                    u64::from_str_radix(&field(status, "SigCgt:"), 16)
+                        .is_ok_and(|mask| mask & (1 << (libc::SIGTERM - 1)) != 0)
+                };
+                let supervisor = loop {
+                    match std::fs::read_to_string(&status) {
+                        Ok(status) if caught(&status) => break status,
+                        _ => std::thread::sleep(std::time::Duration::from_millis(10)),
+                    }
+                };
+                nix::sys::signal::kill(child, nix::sys::signal::Signal::SIGTERM).unwrap();
+                assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+                assert_eq!(field(&supervisor, "Uid:"), "65534 65534 65534 65534");
+                assert_eq!(field(&supervisor, "CapPrm:"), "0000000000000000");
+                assert_eq!(field(&supervisor, "CapEff:"), "0000000000000000");
+            }
+        }
+    }
+
+    #[test]
+    fn test_date_iso_offset() {
+        let config = "[ed]
+name = ed
+rule = .*
+notbefore = 2026-10-18T09:00+01:00
+notafter = 2026-10-18T17:30:00Z
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            vec_eo[0].notbefore,
+            NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(8, 0, 0)
+        );
+        assert_eq!(
+            vec_eo[0].notafter,
+            NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(17, 30, 0)
+        );
+
+        ro.date = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(7, 59, 59)
+            .unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+        ro.date = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+    }
+
+    #[test]
+    fn test_date_section_timezone() {
+        // the timezone applies to dates given before it in the section
+        let config = "[ed]
+name = ed
+rule = .*
+notbefore = 20260701
+notafter = 2026-07-01
+timezone = Europe/London
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(vec_eo[0].timezone, Some("Europe/London".to_string()));
+        assert_eq!(
+            vec_eo[0].notbefore,
+            NaiveDate::from_ymd_opt(2026, 6, 30)
+                .unwrap()
+                .and_hms_opt(23, 0, 0)
+        );
+        assert_eq!(
+            vec_eo[0].notafter,
+            NaiveDate::from_ymd_opt(2026, 7, 1)
+                .unwrap()
+                .and_hms_opt(22, 59, 59)
+        );
+    }
+
+    #[test]
+    fn test_date_default_timezone() {
+        let config = "[default:zone]
+timezone = America/New_York
+permit = false
+
+[ed]
+name = ed
+rule = .*
+notbefore = 20260115
+
+[bob]
+name = bob
+rule = .*
+timezone = UTC
+notbefore = 20260115
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            vec_eo[0].notbefore,
+            NaiveDate::from_ymd_opt(2026, 1, 15)
+                .unwrap()
+                .and_hms_opt(5, 0, 0)
+        );
+        assert_eq!(
+            vec_eo[1].notbefore,
+            NaiveDate::from_ymd_opt(2026, 1, 15)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_date_relative_notafter() {
+        let config = "[ed]
+name = ed
+rule = .*
+notafter = +8h
+notbefore = 20261018090000
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        assert_eq!(
+            vec_eo[0].notafter,
+            NaiveDate::from_ymd_opt(2026, 10, 18)
+                .unwrap()
+                .and_hms_opt(17, 0, 0)
+        );
+
+        let config = "[ed]
+name = ed
+rule = .*
+notafter = +8h
+"
+        .to_string();
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadDate);
+        assert_eq!(errors[0].line, Some(4));
+    }
+
+    #[test]
+    fn test_date_errors() {
+        let config = "[ed]
+name = ed
+rule = .*
+notbefore = 2026101809
+notafter = 2026-13-01
+timezone = Mars/Olympus
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        let kinds: Vec<ConfigErrorKind> = errors.iter().map(|e| e.kind.clone()).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                ConfigErrorKind::BadDate,
+                ConfigErrorKind::BadDate,
+                ConfigErrorKind::BadValue
+            ]
+        );
+        assert_eq!(errors[0].line, Some(4));
+
+        // a time skipped when the clocks go forward
+        let config = "[ed]
+name = ed
+rule = .*
+timezone = Europe/London
+notbefore = 2026-03-29T01:30
+"
+        .to_string();
+        let errors = parse_ini_config_str(&config, &ro).unwrap_err();
+        assert_eq!(errors[0].kind, ConfigErrorKind::BadDate);
+        assert_eq!(errors[0].line, Some(5));
+    }
+
+    #[test]
+    fn test_date_inverted_warning() {
+        let config = "[ed]
+name = ed
+rule = .*
+notbefore = 20261018
+notafter = 20261017
+"
+        .to_string();
+
+        let ro = basic_ro("ed", "root");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let warnings = config_warnings(&vec_eo);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, LintSeverity::Warning);
+        assert_eq!(warnings[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_datematch_timezone() {
+        let config = "[ed]
+name = ed
+rule = .*
+timezone = Asia/Tokyo
+datematch = Fri.* 0[0-8]:.* JST .*
+"
+        .to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, "/bin/bash");
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+
+        // thursday 22:00 UTC is friday 07:00 in Tokyo
+        ro.date = NaiveDate::from_ymd_opt(2026, 10, 15)
+            .unwrap()
+            .and_hms_opt(22, 0, 0)
+            .unwrap();
+        assert!(can(&vec_eo, &mut ro).permit());
+        ro.date = NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(22, 0, 0)
+            .unwrap();
+        assert!(!can(&vec_eo, &mut ro).permit());
+    }
+
+    fn writable_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("please_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_binary_fault() {
+        assert_eq!(binary_fault("/bin/sh"), None);
+
+        let dir = writable_dir("unsafe_binary");
+        let binary = dir.join("tool");
+        std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let link = dir.join("sh");
+        std::os::unix::fs::symlink("/bin/sh", &link).unwrap();
+
+        let binary_fault_found = binary_fault(binary.to_str().unwrap());
+        let link_fault_found = binary_fault(link.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(binary_fault_found
+            .unwrap()
+            .starts_with(&format!("Refusing to run {}", binary.display())));
+        // the link leads somewhere safe, but others may replace the link
+        assert!(link_fault_found.is_some());
+    }
+
+    #[test]
+    fn test_allow_unsafe_binary() {
+        let config = "[default:unsafe]
+name = .*
+rule = .*
+allow_unsafe_binary = true
+permit = false
+
+[ed]
+name = ed
+rule = .*
+permit = true
+
+[bob]
+name = bob
+rule = .*
+permit = true
+allow_unsafe_binary = false
+"
+        .to_string();
+
+        let dir = writable_dir("allow_unsafe_binary");
+        let binary = dir.join("tool");
+        std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+        let binary = binary.to_str().unwrap().to_string();
+
+        let mut ro = basic_ro("ed", "root");
+        basic_cmd(&mut ro, &binary);
+        let vec_eo = parse_ini_config_str(&config, &ro).unwrap();
+        let entry = can(&vec_eo, &mut ro);
+        let open = OpenBinary::open(&ro.cloned_args.as_ref().unwrap()[0]).unwrap();
+        assert!(entry.permit());
+        assert_eq!(unsafe_binary(&entry, &open), None);
+
+        let mut ro = basic_ro("bob", "root");
+        basic_cmd(&mut ro, &binary);
+        let entry = can(&vec_eo, &mut ro);
+        let open = OpenBinary::open(&ro.cloned_args.as_ref().unwrap()[0]).unwrap();
+        let fault = unsafe_binary(&entry, &open);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(entry.permit());
+        assert!(fault.is_some());
+
+        let findings = lint_config(&vec_eo, &ro);
+        assert_eq!(
+            findings
+                .iter()
+                .filter(|f| f.message.contains("allow_unsafe_binary"))
+                .count(),
+            1
+        );
+    }
 }